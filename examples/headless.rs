@@ -0,0 +1,28 @@
+use easy_sdl3_text as sdl3_text;
+use ab_glyph::FontRef;
+use sdl3::{pixels::{Color, PixelFormat}, render::Canvas, surface::Surface, sys::pixels::SDL_PixelFormat};
+pub use anyhow::*;
+
+
+
+// Renders text into an offscreen surface and saves it as an image, without ever opening a window (useful for thumbnails, server-side image generation, or tests on machines without a display)
+fn main() -> Result<()> {
+	
+	// a software canvas doesn't need a video subsystem
+	let surface = Surface::new(800, 200, unsafe {PixelFormat::from_ll(SDL_PixelFormat::ABGR8888)})?;
+	let mut canvas = Canvas::from_surface(surface)?;
+	let texture_creator = canvas.texture_creator();
+	let font = FontRef::try_from_slice(include_bytes!("resources/Inter_24pt-Regular.ttf"))?;
+	// the cache's textures belong to the surface's renderer, so it's created for the surface's context instead of a window's
+	let mut text_cache = sdl3_text::TextCache::new_for_context(font, vec!());
+	
+	canvas.set_draw_color(Color::RGB(255, 255, 255));
+	canvas.clear();
+	let mut text_settings = sdl3_text::TextRenderingSettings::new_subpixel(40, sdl3_text::HAlign::Center, sdl3_text::VAlign::Center, Color::RGB(30, 30, 30), Color::RGB(255, 255, 255), &mut canvas, &texture_creator, &mut text_cache);
+	sdl3_text::render_text_subpixel("Rendered without a window", 400.0, 100.0, &mut text_settings)?;
+	
+	canvas.present();
+	canvas.into_surface().save_bmp("headless.bmp")?;
+	println!("Saved to headless.bmp");
+	Ok(())
+}
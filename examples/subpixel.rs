@@ -95,7 +95,7 @@ pub fn draw<'a, F: sdl3_text::ThreadSafeFont>(canvas: &mut Canvas<Window>, textu
 	let mut size = scale * 0.1;
 	let mut y = size;
 	while size > 10.0 {
-		sdl3_text::render_text_subpixel("Example text 1234567890 !@#$%^&*()_+-=[]{}|;:',.<>/?~", (scale * 0.1) as i32, y as i32, &mut text_settings)?;
+		sdl3_text::render_text_subpixel("Example text 1234567890 !@#$%^&*()_+-=[]{}|;:',.<>/?~", scale * 0.1, y, &mut text_settings)?;
 		size *= 0.8;
 		text_settings.size = size;
 		y += size * 1.3;
@@ -0,0 +1,188 @@
+use crate::*;
+use sdl3::{pixels::PixelFormat, rect::Rect, render::{Texture, TextureAccess, TextureCreator}, sys::pixels::SDL_PixelFormat};
+
+
+
+/// The default width and height of each atlas page
+pub(crate) const ATLAS_PAGE_SIZE: u32 = 1024;
+/// Empty space around each glyph so that linear filtering doesn't bleed neighboring glyphs into each other
+pub(crate) const ATLAS_PADDING: u32 = 1;
+
+
+
+/// The location of a cached glyph inside the atlas, plus the offsets needed to draw it
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct CachedGlyph {
+	pub(crate) page: usize,
+	pub(crate) src: Rect,
+	pub(crate) x_offset: f32,
+	pub(crate) y_offset: f32,
+	/// The value of `TextCache::use_counter` when this glyph was last drawn (used for least-recently-used eviction)
+	pub(crate) last_used: u64,
+	/// Pinned glyphs are never evicted
+	pub(crate) pinned: bool,
+}
+
+impl CachedGlyph {
+	/// The amount of atlas texture memory that this glyph takes up (including padding)
+	pub(crate) fn byte_size(&self) -> usize {
+		(self.src.width() + ATLAS_PADDING * 2) as usize * (self.src.height() + ATLAS_PADDING * 2) as usize * 4
+	}
+	/// Marks the glyph as used by the current render call
+	pub(crate) fn touch(&mut self, use_counter: u64, pin: bool) {
+		self.last_used = use_counter;
+		self.pinned |= pin;
+	}
+}
+
+
+
+/// Packs glyphs into a few large textures (pages) using shelf packing, so that whole strings can be drawn from one or two textures
+pub(crate) struct GlyphAtlas<'a> {
+	pub(crate) pages: Vec<AtlasPage<'a>>,
+}
+
+pub(crate) struct AtlasPage<'a> {
+	pub(crate) texture: Texture<'a>,
+	pub(crate) width: u32,
+	pub(crate) height: u32,
+	shelves: Vec<Shelf>,
+	next_shelf_y: u32,
+	/// Padded rects of evicted glyphs, which can be reused by new glyphs
+	free_rects: Vec<Rect>,
+	glyph_count: usize,
+}
+
+struct Shelf {
+	y: u32,
+	height: u32,
+	next_x: u32,
+}
+
+impl<'a> GlyphAtlas<'a> {
+	pub(crate) fn new() -> Self {
+		Self {
+			pages: vec!(),
+		}
+	}
+	pub(crate) fn clear(&mut self) {
+		self.pages.clear();
+	}
+	/// Copies the given ABGR8888 pixels into the atlas (creating a new page if needed) and returns the page index and the glyph's rect within that page
+	pub(crate) fn insert<T>(&mut self, pixels: &[u8], width: u32, height: u32, texture_creator: &'a TextureCreator<T>) -> Result<(usize, Rect), RenderTextError> {
+		let padded_width = width + ATLAS_PADDING * 2;
+		let padded_height = height + ATLAS_PADDING * 2;
+		
+		let mut location = None;
+		for (i, page) in self.pages.iter_mut().enumerate() {
+			if let Some((x, y)) = page.allocate(padded_width, padded_height) {
+				location = Some((i, x, y));
+				break;
+			}
+		}
+		let (page_index, x, y) = match location {
+			Some(location) => location,
+			None => {
+				let mut page = AtlasPage::new(padded_width.max(ATLAS_PAGE_SIZE), padded_height.max(ATLAS_PAGE_SIZE), texture_creator)?;
+				let (x, y) = page.allocate(padded_width, padded_height).expect("new atlas page is always large enough for the glyph it was created for");
+				self.pages.push(page);
+				(self.pages.len() - 1, x, y)
+			}
+		};
+		
+		self.pages[page_index].glyph_count += 1;
+		let padded_pixels = pad_pixels(pixels, width, height);
+		let padded_rect = Rect::new(x as i32, y as i32, padded_width, padded_height);
+		self.pages[page_index].texture.update(padded_rect, &padded_pixels, padded_width as usize * 4)?;
+		
+		Ok((page_index, Rect::new((x + ATLAS_PADDING) as i32, (y + ATLAS_PADDING) as i32, width, height)))
+	}
+	/// Frees the glyph's space so that it can be reused (the texture itself isn't changed, since the space is overwritten when it's reused)
+	pub(crate) fn remove(&mut self, glyph: &CachedGlyph) {
+		let page = &mut self.pages[glyph.page];
+		page.glyph_count -= 1;
+		if page.glyph_count == 0 {
+			page.shelves.clear();
+			page.next_shelf_y = 0;
+			page.free_rects.clear();
+			return;
+		}
+		let src = glyph.src;
+		page.free_rects.push(Rect::new(src.x() - ATLAS_PADDING as i32, src.y() - ATLAS_PADDING as i32, src.width() + ATLAS_PADDING * 2, src.height() + ATLAS_PADDING * 2));
+	}
+}
+
+impl<'a> AtlasPage<'a> {
+	fn new<T>(width: u32, height: u32, texture_creator: &'a TextureCreator<T>) -> Result<Self, RenderTextError> {
+		let texture = texture_creator.create_texture(
+			Some(unsafe {PixelFormat::from_ll(SDL_PixelFormat::ABGR8888)}),
+			TextureAccess::Static,
+			width,
+			height,
+		)?;
+		Ok(Self {
+			texture,
+			width,
+			height,
+			shelves: vec!(),
+			next_shelf_y: 0,
+			free_rects: vec!(),
+			glyph_count: 0,
+		})
+	}
+	/// Reuses the smallest freed rect that the rect fits into, otherwise finds the shortest shelf that the rect fits into, or opens a new shelf if none fit
+	fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+		if width > self.width || height > self.height {return None;}
+		
+		let best_free_rect = self.free_rects.iter().enumerate()
+			.filter(|(_i, rect)| rect.width() >= width && rect.height() >= height && rect.height() <= height * 2)
+			.min_by_key(|(_i, rect)| rect.width() * rect.height())
+			.map(|(i, _rect)| i);
+		if let Some(i) = best_free_rect {
+			let rect = self.free_rects.swap_remove(i);
+			return Some((rect.x() as u32, rect.y() as u32));
+		}
+		
+		let page_width = self.width;
+		let best_shelf = self.shelves.iter_mut()
+			.filter(|shelf| shelf.height >= height && shelf.height <= height * 2 && shelf.next_x + width <= page_width)
+			.min_by_key(|shelf| shelf.height);
+		if let Some(shelf) = best_shelf {
+			let x = shelf.next_x;
+			shelf.next_x += width;
+			return Some((x, shelf.y));
+		}
+		
+		if self.next_shelf_y + height > self.height {return None;}
+		let y = self.next_shelf_y;
+		self.shelves.push(Shelf {
+			y,
+			height,
+			next_x: width,
+		});
+		self.next_shelf_y += height;
+		Some((0, y))
+	}
+}
+
+
+
+/// Adds `ATLAS_PADDING` pixels around the image, which copy the color of the nearest edge pixel but are fully transparent (this keeps the edge colors correct when the texture is linearly filtered)
+fn pad_pixels(pixels: &[u8], width: u32, height: u32) -> Vec<u8> {
+	let (width, height, padding) = (width as usize, height as usize, ATLAS_PADDING as usize);
+	let padded_width = width + padding * 2;
+	let padded_height = height + padding * 2;
+	let mut output = vec![0; padded_width * padded_height * 4];
+	for y in 0..padded_height {
+		let src_y = y.saturating_sub(padding).min(height - 1);
+		for x in 0..padded_width {
+			let src_x = x.saturating_sub(padding).min(width - 1);
+			let src_i = (src_x + src_y * width) * 4;
+			let dst_i = (x + y * padded_width) * 4;
+			output[dst_i .. dst_i + 4].copy_from_slice(&pixels[src_i .. src_i + 4]);
+			let is_padding = x < padding || y < padding || x >= width + padding || y >= height + padding;
+			if is_padding {output[dst_i + 3] = 0;}
+		}
+	}
+	output
+}
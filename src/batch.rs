@@ -0,0 +1,71 @@
+use crate::*;
+use sdl3::{pixels::{Color, FColor}, render::{BlendMode, Canvas, FPoint, FRect, RenderTarget, Vertex}, sys::{blendmode::SDL_BlendMode, render::SDL_SetTextureBlendMode}};
+
+
+
+/// Collects the quads for many glyphs so they can be submitted with one `render_geometry()` call per atlas page instead of one `copy()` per glyph
+pub(crate) struct GlyphBatch {
+	// page index -> (vertices, indices)
+	pages: Vec<(Vec<Vertex>, Vec<i32>)>,
+}
+
+impl GlyphBatch {
+	pub(crate) fn new() -> Self {
+		Self {
+			pages: vec!(),
+		}
+	}
+	/// Adds a quad that draws the glyph's atlas rect to `dst`, with the texture modulated by `color`
+	pub(crate) fn push_glyph(&mut self, glyph: &CachedGlyph, atlas: &GlyphAtlas, dst: FRect, color: Color) {
+		if self.pages.len() <= glyph.page {
+			self.pages.resize_with(glyph.page + 1, || (vec!(), vec!()));
+		}
+		let page = &atlas.pages[glyph.page];
+		let (page_width, page_height) = (page.width as f32, page.height as f32);
+		let src = glyph.src;
+		let (u1, v1) = (src.x() as f32 / page_width, src.y() as f32 / page_height);
+		let (u2, v2) = ((src.x() + src.width() as i32) as f32 / page_width, (src.y() + src.height() as i32) as f32 / page_height);
+		let (x1, y1, x2, y2) = (dst.x, dst.y, dst.x + dst.w, dst.y + dst.h);
+		let color = FColor::from(color);
+		
+		let (vertices, indices) = &mut self.pages[glyph.page];
+		let first_index = vertices.len() as i32;
+		vertices.push(Vertex {position: FPoint::new(x1, y1), color, tex_coord: FPoint::new(u1, v1)});
+		vertices.push(Vertex {position: FPoint::new(x2, y1), color, tex_coord: FPoint::new(u2, v1)});
+		vertices.push(Vertex {position: FPoint::new(x2, y2), color, tex_coord: FPoint::new(u2, v2)});
+		vertices.push(Vertex {position: FPoint::new(x1, y2), color, tex_coord: FPoint::new(u1, v2)});
+		indices.extend_from_slice(&[first_index, first_index + 1, first_index + 2, first_index, first_index + 2, first_index + 3]);
+	}
+	/// Changes the color of every collected quad, so the same quads can be drawn again in another pass
+	pub(crate) fn set_color(&mut self, color: Color) {
+		let color = FColor::from(color);
+		for (vertices, _indices) in &mut self.pages {
+			for vertex in vertices {
+				vertex.color = color;
+			}
+		}
+	}
+	/// Submits all collected quads to the canvas
+	pub(crate) fn draw<T: RenderTarget>(&self, canvas: &mut Canvas<T>, atlas: &GlyphAtlas) -> Result<(), RenderTextError> {
+		for (page_index, (vertices, indices)) in self.pages.iter().enumerate() {
+			if vertices.is_empty() {continue;}
+			canvas.render_geometry(vertices, Some(&atlas.pages[page_index].texture), indices.as_slice())?;
+		}
+		Ok(())
+	}
+	/// Same as `draw()`, but with a custom blend mode (from `SDL_ComposeCustomBlendMode()`), and the atlas textures are set back to regular alpha blending afterwards
+	pub(crate) fn draw_with_blend_mode<T: RenderTarget>(&self, canvas: &mut Canvas<T>, atlas: &GlyphAtlas, blend_mode: SDL_BlendMode) -> Result<(), RenderTextError> {
+		for (page_index, (vertices, indices)) in self.pages.iter().enumerate() {
+			if vertices.is_empty() {continue;}
+			let texture = &atlas.pages[page_index].texture;
+			// the sdl3 crate only wraps the built-in blend modes, so custom ones have to be set directly
+			if !unsafe {SDL_SetTextureBlendMode(texture.raw(), blend_mode)} {
+				return Err(sdl3::get_error().into());
+			}
+			let result = canvas.render_geometry(vertices, Some(texture), indices.as_slice());
+			unsafe {SDL_SetTextureBlendMode(texture.raw(), BlendMode::Blend as u32)};
+			result?;
+		}
+		Ok(())
+	}
+}
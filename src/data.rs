@@ -1,42 +1,62 @@
 use crate::*;
-use std::collections::{HashMap, HashSet};
-use sdl3::{pixels::Color, render::{Canvas, Texture, TextureCreator, TextureValueError, UpdateTextureError}, video::{Window, WindowContext}, Error};
-use ab_glyph::Font;
+use std::{collections::{HashMap, HashSet}, marker::PhantomData, time::Duration};
+use sdl3::{pixels::Color, render::{Canvas, RenderTarget, TextureCreator, TextureValueError, UpdateTextureError}, video::{Window, WindowContext}, Error};
+use ab_glyph::{Font, GlyphId};
 
 
 
-/// This holds most of the arguments to `render_text_regular()` and `render_text_subpixel()`
+/// This holds most of the arguments to `render_text_regular()`, `render_text_subpixel()`, and `render_text_sdf()`
 /// 
 /// These arguments (fields) are each likely to not change from call to call
-pub struct TextRenderingSettings<'a, 'b, F: ThreadSafeFont> {
+/// 
+/// This can render to any sdl3 render target, so for example a `Canvas<Surface>` can be used for offscreen / headless rendering
+pub struct TextRenderingSettings<'a, 'b, F: ThreadSafeFont, T: RenderTarget = Window> {
 	/// NOTE: for `render_text_subpixel()`, this is converted to u32 (this is don to significantly cut down on the number of character textures to rasterize and cache)
 	pub size: f32,
-	#[allow(missing_docs)]
+	/// Multiplier for the distance between lines (which defaults to the font's line height plus its line gap)
+	pub line_spacing: f32,
+	/// If set, lines that are wider than this are wrapped (at Unicode line break opportunities when possible, otherwise between chars)
+	pub max_width: Option<f32>,
+	/// For multi-line text, this is applied to each line separately
 	pub h_align: HAlign,
-	#[allow(missing_docs)]
+	/// For multi-line text, this is applied to the whole block of lines
 	pub v_align: VAlign,
 	#[allow(missing_docs)]
 	pub foreground: Color,
 	/// This only exists for `render_text_subpixel()`, with `render_text_regular()` you can set this to whatever you want and it won't affect anything
 	pub background: Color,
+	/// The order of the display's sub-pixels, only used for sub-pixel rendering (this defaults to `SubpixelOrder::Rgb`, which is what most displays use)
+	pub subpixel_order: SubpixelOrder,
+	/// The filter and gamma used for sub-pixel rendering, which can be changed to match the look of each platform's native text (this defaults to `SubpixelFilter::DEFAULT`)
+	pub subpixel_filter: SubpixelFilter,
+	/// If set, regular and (pre-blended) sub-pixel text is blended in linear light instead of directly in sRGB, which makes light-on-dark and dark-on-light text look equally thick (this is `None` by default, which keeps the original look)
+	pub gamma_correction: Option<GammaCorrection>,
+	/// How wide the anti-aliased edges are for `render_text_sdf()`, in pixels measured at the power-of-two size that glyphs are cached at (1.0 looks about as sharp as `render_text_regular()`, and larger values look softer)
+	pub edge_softness: f32,
 	#[allow(missing_docs)]
-	pub canvas: &'a mut Canvas<Window>,
-	#[allow(missing_docs)]
-	pub texture_creator: &'b TextureCreator<WindowContext>,
+	pub canvas: &'a mut Canvas<T>,
+	/// NOTE: this must be the texture creator of `canvas`
+	pub texture_creator: &'b TextureCreator<T::Context>,
 	#[allow(missing_docs)]
-	pub text_cache: &'a mut TextCache<'b, F>,
+	pub text_cache: &'a mut TextCache<'b, F, T::Context>,
 }
 
-impl<'a, 'b, F: ThreadSafeFont> TextRenderingSettings<'a, 'b, F> {
+impl<'a, 'b, F: ThreadSafeFont, T: RenderTarget> TextRenderingSettings<'a, 'b, F, T> {
 	/// Creates a new `TextRenderingSettings` that is meant to be used with `render_text_regular()`, but can also be used for subpixel rendering
 	#[allow(clippy::too_many_arguments)]
-	pub fn new_regular(size: f32, h_align: impl Into<HAlign>, v_align: impl Into<VAlign>, foreground: impl Into<Color>, canvas: &'a mut Canvas<Window>, texture_creator: &'b TextureCreator<WindowContext>, text_cache: &'a mut TextCache<'b, F>) -> Self {
+	pub fn new_regular(size: f32, h_align: impl Into<HAlign>, v_align: impl Into<VAlign>, foreground: impl Into<Color>, canvas: &'a mut Canvas<T>, texture_creator: &'b TextureCreator<T::Context>, text_cache: &'a mut TextCache<'b, F, T::Context>) -> Self {
 		Self {
 			size,
+			line_spacing: 1.0,
+			max_width: None,
 			h_align: h_align.into(),
 			v_align: v_align.into(),
 			foreground: foreground.into(),
 			background: Color::RGB(127, 127, 127),
+			subpixel_order: SubpixelOrder::Rgb,
+			subpixel_filter: SubpixelFilter::DEFAULT,
+			gamma_correction: None,
+			edge_softness: 1.0,
 			canvas,
 			texture_creator,
 			text_cache,
@@ -44,13 +64,19 @@ impl<'a, 'b, F: ThreadSafeFont> TextRenderingSettings<'a, 'b, F> {
 	}
 	/// Creates a new `TextRenderingSettings` that is meant to be used with `render_text_subpixel()`, but can also be used for regular rendering
 	#[allow(clippy::too_many_arguments)]
-	pub fn new_subpixel(size: u32, h_align: impl Into<HAlign>, v_align: impl Into<VAlign>, foreground: impl Into<Color>, background: impl Into<Color>, canvas: &'a mut Canvas<Window>, texture_creator: &'b TextureCreator<WindowContext>, text_cache: &'a mut TextCache<'b, F>) -> Self {
+	pub fn new_subpixel(size: u32, h_align: impl Into<HAlign>, v_align: impl Into<VAlign>, foreground: impl Into<Color>, background: impl Into<Color>, canvas: &'a mut Canvas<T>, texture_creator: &'b TextureCreator<T::Context>, text_cache: &'a mut TextCache<'b, F, T::Context>) -> Self {
 		Self {
 			size: size as f32,
+			line_spacing: 1.0,
+			max_width: None,
 			h_align: h_align.into(),
 			v_align: v_align.into(),
 			foreground: foreground.into(),
 			background: background.into(),
+			subpixel_order: SubpixelOrder::Rgb,
+			subpixel_filter: SubpixelFilter::DEFAULT,
+			gamma_correction: None,
+			edge_softness: 1.0,
 			canvas,
 			texture_creator,
 			text_cache,
@@ -67,33 +93,97 @@ impl<F: Font + Send + Sync> ThreadSafeFont for F {}
 
 
 
-/// A cache for character textures (also holds the font)
-pub struct TextCache<'a, F: ThreadSafeFont> {
-	// (char, foreground) -> (texture, width, height, x_offset, y_offset)
-	pub(crate) map_regular: HashMap<(char, Color), (Texture<'a>, u32, u32, f32, f32)>,
-	pub(crate) set_regular: HashSet<(char, Color)>,
+/// A cache for character textures (also holds the font and any fallback fonts)
+/// 
+/// All rasterized characters are packed into a few shared atlas textures, so most strings can be drawn from just one or two textures
+/// 
+/// `C` is the context type of the texture creator that the characters are uploaded with (`WindowContext` by default, or `SurfaceContext` for a `Canvas<Surface>`), so a cache can't be used with a canvas of a different kind
+/// 
+/// NOTE: the cached textures belong to the renderer that created them, so each canvas (for example a window canvas and an offscreen surface canvas) needs its own TextCache, even if they're the same kind of canvas
+pub struct TextCache<'a, F: ThreadSafeFont, C = WindowContext> {
+	pub(crate) map_regular: HashMap<RegularKey, CachedGlyph>,
+	pub(crate) set_regular: HashSet<RegularKey>,
 	// NOTE: this can kinda look a bit nicer if `size` here is replaced with usize and `size` as input for `render_text_*()` is replaced with f32 (which allows for better text scaling), but that significantly increases the number of textures to rasterize and store
-	// (char, size, foreground, background) -> (texture, width, height, x_offset, y_offset)
-	pub(crate) map_subpixel: HashMap<(char, u32, Color, Color), (Texture<'a>, u32, u32, f32, f32)>,
-	pub(crate) set_subpixel: HashSet<(char, u32, Color, Color)>,
-	pub(crate) font: F,
+	pub(crate) map_subpixel: HashMap<SubpixelKey, CachedGlyph>,
+	pub(crate) set_subpixel: HashSet<SubpixelKey>,
+	// (font index, glyph) -> distance field (`None` for glyphs without an outline), these are kept until the cache is cleared
+	pub(crate) sdf_fields: HashMap<(usize, GlyphId), Option<SdfGlyph>>,
+	// (font index, glyph, power-of-two size bucket, edge softness) -> location in atlas (these are white like regular glyphs)
+	pub(crate) map_sdf: HashMap<(usize, GlyphId, u32, u32), CachedGlyph>,
+	pub(crate) set_sdf: HashSet<(usize, GlyphId, u32, u32)>,
+	pub(crate) atlas: GlyphAtlas<'a>,
+	// the main font followed by the fallback fonts (this is never empty)
+	pub(crate) fonts: Vec<F>,
+	pub(crate) limits: CacheLimits,
+	// the total `CachedGlyph::byte_size()` of all cached glyphs
+	pub(crate) cached_bytes: usize,
+	// incremented on every render call, so that glyphs can be sorted by when they were last used
+	pub(crate) use_counter: u64,
+	pub(crate) pin_glyphs: bool,
+	// the counters and timings (the glyph counts and sizes are filled in by `stats()`)
+	pub(crate) stats: CacheStats,
+	// every texture in the atlas was created by a `TextureCreator<C>`
+	pub(crate) context: PhantomData<&'a TextureCreator<C>>,
 }
 
+// (font index, glyph, size bucket, sub-pixel position, coverage curve) -> location in atlas (regular glyphs are white, and are colored when drawn)
+pub(crate) type RegularKey = (usize, GlyphId, u32, u32, CoverageCurve);
+// (font index, glyph, size, foreground, background, sub-pixel position, sub-pixel order, filter, gamma correction) -> location in atlas
+pub(crate) type SubpixelKey = (usize, GlyphId, u32, Color, Color, u32, SubpixelOrder, SubpixelFilter, Option<GammaCorrection>);
+
 impl<'a, F: ThreadSafeFont> TextCache<'a, F> {
-	/// Creates a new TextCache
+	/// Creates a new TextCache for a window canvas
 	#[inline]
 	pub fn new(font: F) -> Self {
+		Self::new_with_fallbacks(font, vec!())
+	}
+	/// Creates a new TextCache for a window canvas, with fallback fonts, which are used (in order) for any characters that the main font doesn't have
+	#[inline]
+	pub fn new_with_fallbacks(font: F, fallback_fonts: Vec<F>) -> Self {
+		Self::new_for_context(font, fallback_fonts)
+	}
+}
+
+impl<'a, F: ThreadSafeFont, C> TextCache<'a, F, C> {
+	/// Creates a new TextCache for any kind of canvas (for example a `Canvas<Surface>`, whose texture creator has a `SurfaceContext`), with optional fallback fonts
+	/// 
+	/// The context type can usually be inferred from the `TextRenderingSettings` that the cache is used with
+	pub fn new_for_context(font: F, fallback_fonts: Vec<F>) -> Self {
+		let mut fonts = Vec::with_capacity(fallback_fonts.len() + 1);
+		fonts.push(font);
+		fonts.extend(fallback_fonts);
 		Self {
 			map_regular: HashMap::new(),
 			set_regular: HashSet::new(),
 			map_subpixel: HashMap::new(),
 			set_subpixel: HashSet::new(),
-			font,
+			sdf_fields: HashMap::new(),
+			map_sdf: HashMap::new(),
+			set_sdf: HashSet::new(),
+			atlas: GlyphAtlas::new(),
+			fonts,
+			limits: CacheLimits::default(),
+			cached_bytes: 0,
+			use_counter: 0,
+			pin_glyphs: false,
+			stats: CacheStats::default(),
+			context: PhantomData,
 		}
 	}
 	/// Switches this cache to a different font (and clears the cache so the characters can be re-rendered)
+	/// 
+	/// NOTE: this keeps any fallback fonts
 	pub fn switch_font(&mut self, new_font: F) {
-		self.font = new_font;
+		self.fonts[0] = new_font;
+		self.clear();
+	}
+	/// Adds a fallback font to the end of the fallback chain (this doesn't need to clear the cache)
+	pub fn add_fallback_font(&mut self, font: F) {
+		self.fonts.push(font);
+	}
+	/// Removes all fallback fonts (and clears the cache)
+	pub fn clear_fallback_fonts(&mut self) {
+		self.fonts.truncate(1);
 		self.clear();
 	}
 	/// Clears the cache, probably should only be done if the program is actually low on ram or vram
@@ -102,11 +192,205 @@ impl<'a, F: ThreadSafeFont> TextCache<'a, F> {
 		self.set_regular.clear();
 		self.map_subpixel.clear();
 		self.set_subpixel.clear();
+		self.sdf_fields.clear();
+		self.map_sdf.clear();
+		self.set_sdf.clear();
+		self.atlas.clear();
+		self.cached_bytes = 0;
+	}
+	/// Sets how many glyphs this cache can hold before the least recently used glyphs are evicted (this can evict glyphs immediately)
+	pub fn set_limits(&mut self, limits: CacheLimits) {
+		self.limits = limits;
+		self.use_counter += 1;
+		self.evict_if_needed();
+	}
+	/// Gets the limits set with `set_limits()` (which has no limits by default)
+	pub fn limits(&self) -> CacheLimits {
+		self.limits
+	}
+	/// While this is enabled, every glyph that is drawn gets pinned, meaning that it will never be evicted (until `unpin_all()` or `clear()` is called)
+	/// 
+	/// This is useful for making sure that things like ui text never have to be re-rasterized
+	pub fn set_pinning(&mut self, pin_glyphs: bool) {
+		self.pin_glyphs = pin_glyphs;
+	}
+	/// Gets the cache's current statistics, which can be used to find out when and why characters are being rasterized
+	pub fn stats(&self) -> CacheStats {
+		CacheStats {
+			regular_glyphs: self.map_regular.len(),
+			subpixel_glyphs: self.map_subpixel.len(),
+			sdf_glyphs: self.map_sdf.len(),
+			sdf_fields: self.sdf_fields.len(),
+			glyph_bytes: self.cached_bytes,
+			atlas_pages: self.atlas.pages.len(),
+			atlas_bytes: self.atlas.pages.iter().map(|page| page.width as usize * page.height as usize * 4).sum(),
+			..self.stats.clone()
+		}
+	}
+	/// Resets the per-frame statistics (`CacheStats::frame_*`), which should be called once at the start of each frame
+	pub fn reset_frame_stats(&mut self) {
+		self.stats.frame_hits = 0;
+		self.stats.frame_rasterizations = 0;
+		self.stats.frame_rasterize_time = Duration::ZERO;
+		self.stats.frame_upload_time = Duration::ZERO;
+	}
+	/// Un-pins all pinned glyphs, so that they can be evicted again
+	pub fn unpin_all(&mut self) {
+		for glyph in self.map_regular.values_mut().chain(self.map_subpixel.values_mut()).chain(self.map_sdf.values_mut()) {
+			glyph.pinned = false;
+		}
+	}
+	/// Measures text without drawing it or rasterizing any characters (this only needs the font, so it can be used before anything is rendered)
+	pub fn measure_text(&self, text: impl AsRef<str>, size: f32) -> TextMetrics {
+		self.measure_text_with(text, size, 1.0, None)
+	}
+	/// Same as `measure_text()`, but with a custom line spacing multiplier and wrapping width (see `TextRenderingSettings::line_spacing` and `TextRenderingSettings::max_width`)
+	pub fn measure_text_with(&self, text: impl AsRef<str>, size: f32, line_spacing: f32, max_width: Option<f32>) -> TextMetrics {
+		TextLayout::new_with(text, size, line_spacing, max_width, self).metrics()
+	}
+	/// Adds a rasterized glyph to the atlas and returns its cache entry
+	pub(crate) fn upload_glyph(&mut self, pixels: &[u8], width: u32, height: u32, x_offset: f32, y_offset: f32, texture_creator: &'a TextureCreator<C>) -> Result<CachedGlyph, RenderTextError> {
+		let (page, src) = self.atlas.insert(pixels, width, height, texture_creator)?;
+		let glyph = CachedGlyph {page, src, x_offset, y_offset, last_used: self.use_counter, pinned: self.pin_glyphs};
+		self.cached_bytes += glyph.byte_size();
+		Ok(glyph)
+	}
+	/// If the cache is over its limits, this evicts the least recently used glyphs (excluding pinned glyphs and glyphs used by the current render call) until the cache is down to 3/4 of its limits, so that eviction doesn't have to happen on every call
+	pub(crate) fn evict_if_needed(&mut self) {
+		let mut glyph_count = self.map_regular.len() + self.map_subpixel.len() + self.map_sdf.len();
+		let is_over_count = self.limits.max_glyphs.is_some_and(|max| glyph_count > max);
+		let is_over_bytes = self.limits.max_bytes.is_some_and(|max| self.cached_bytes > max);
+		if !is_over_count && !is_over_bytes {return;}
+		let target_count = self.limits.max_glyphs.map(|max| max / 4 * 3).unwrap_or(usize::MAX);
+		let target_bytes = self.limits.max_bytes.map(|max| max / 4 * 3).unwrap_or(usize::MAX);
+		
+		let regular_keys = self.map_regular.iter().filter(|(_key, glyph)| self.is_evictable(glyph)).map(|(key, glyph)| (glyph.last_used, *key)).collect::<Vec<_>>();
+		let subpixel_keys = self.map_subpixel.iter().filter(|(_key, glyph)| self.is_evictable(glyph)).map(|(key, glyph)| (glyph.last_used, *key)).collect::<Vec<_>>();
+		let sdf_keys = self.map_sdf.iter().filter(|(_key, glyph)| self.is_evictable(glyph)).map(|(key, glyph)| (glyph.last_used, *key)).collect::<Vec<_>>();
+		// (last used, which cache, index into its keys)
+		let mut candidates = regular_keys.iter().enumerate().map(|(i, (last_used, _key))| (*last_used, 0, i))
+			.chain(subpixel_keys.iter().enumerate().map(|(i, (last_used, _key))| (*last_used, 1, i)))
+			.chain(sdf_keys.iter().enumerate().map(|(i, (last_used, _key))| (*last_used, 2, i)))
+			.collect::<Vec<_>>();
+		candidates.sort_unstable_by_key(|(last_used, _cache, _i)| *last_used);
+		
+		for (_last_used, cache, i) in candidates {
+			if glyph_count <= target_count && self.cached_bytes <= target_bytes {break;}
+			let glyph = match cache {
+				0 => {
+					let key = regular_keys[i].1;
+					self.set_regular.remove(&key);
+					self.map_regular.remove(&key)
+				}
+				1 => {
+					let key = subpixel_keys[i].1;
+					self.set_subpixel.remove(&key);
+					self.map_subpixel.remove(&key)
+				}
+				_ => {
+					let key = sdf_keys[i].1;
+					self.set_sdf.remove(&key);
+					self.map_sdf.remove(&key)
+				}
+			};
+			let Some(glyph) = glyph else {continue;};
+			self.atlas.remove(&glyph);
+			self.cached_bytes -= glyph.byte_size();
+			self.stats.evictions += 1;
+			glyph_count -= 1;
+		}
+	}
+	/// Adds the results of one render call to the statistics
+	pub(crate) fn record_stats(&mut self, hits: u64, misses: u64, rasterize_time: Duration, upload_time: Duration) {
+		self.stats.hits += hits;
+		self.stats.misses += misses;
+		self.stats.frame_hits += hits;
+		self.stats.frame_rasterizations += misses;
+		self.stats.frame_rasterize_time += rasterize_time;
+		self.stats.frame_upload_time += upload_time;
+	}
+	fn is_evictable(&self, glyph: &CachedGlyph) -> bool {
+		!glyph.pinned && glyph.last_used < self.use_counter
 	}
 }
 
 
 
+/// Statistics about a `TextCache`, as returned by `TextCache::stats()`
+/// 
+/// The `frame_*` fields count up until `TextCache::reset_frame_stats()` is called, and everything else counts up for the whole life of the cache
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CacheStats {
+	/// The number of glyphs currently in the regular cache
+	pub regular_glyphs: usize,
+	/// The number of glyphs currently in the sub-pixel cache
+	pub subpixel_glyphs: usize,
+	/// The number of glyphs currently in the sdf cache (each glyph is cached once per power-of-two size and edge softness)
+	pub sdf_glyphs: usize,
+	/// The number of distance fields used by `render_text_sdf()`, which are kept on the cpu and aren't affected by `CacheLimits`
+	pub sdf_fields: usize,
+	/// The (approximate) amount of atlas texture memory used by cached glyphs, in bytes
+	pub glyph_bytes: usize,
+	/// The number of atlas textures
+	pub atlas_pages: usize,
+	/// The total size of all atlas textures, in bytes
+	pub atlas_bytes: usize,
+	/// The number of glyphs that were drawn from the cache
+	pub hits: u64,
+	/// The number of glyphs that weren't cached, and so had to be rasterized
+	pub misses: u64,
+	/// The number of glyphs that were evicted because of `CacheLimits`
+	pub evictions: u64,
+	/// The number of hits since the last `reset_frame_stats()`
+	pub frame_hits: u64,
+	/// The number of glyphs rasterized since the last `reset_frame_stats()` (every miss is rasterized, so this is also the number of misses)
+	pub frame_rasterizations: u64,
+	/// The time spent rasterizing glyphs since the last `reset_frame_stats()`
+	pub frame_rasterize_time: Duration,
+	/// The time spent uploading glyphs to the atlas since the last `reset_frame_stats()`
+	pub frame_upload_time: Duration,
+}
+
+
+
+/// Limits for how much a `TextCache` can hold before the least recently used glyphs are evicted (`None` means no limit)
+/// 
+/// Since the sub-pixel cache is keyed on size and colors, animating the size or colors of sub-pixel text can fill the cache very quickly without limits
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct CacheLimits {
+	/// The maximum number of cached glyphs (regular, sub-pixel, and sdf combined)
+	pub max_glyphs: Option<usize>,
+	/// The maximum (approximate) amount of texture memory used by cached glyphs, in bytes
+	/// 
+	/// NOTE: the atlas textures are never shrunk, evicted glyphs just leave space for new glyphs
+	pub max_bytes: Option<usize>,
+}
+
+
+
+/// The measurements of a piece of text, as returned by `measure_text()`
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextMetrics {
+	/// The total width of the text (including kerning and extra character spacing), which is the width of the widest line for multi-line text
+	pub width: f32,
+	/// The total height of all lines (for single-line text, this is the font's height at this size, `ascent - descent`)
+	pub height: f32,
+	/// The number of lines (text is split on `\n` and `\r\n`, and wrapped if `max_width` is set)
+	pub line_count: usize,
+	/// The distance from the baseline to the top of the tallest characters
+	pub ascent: f32,
+	/// The distance from the baseline to the bottom of the lowest characters (usually negative)
+	pub descent: f32,
+	/// The font's recommended extra space between lines
+	pub line_gap: f32,
+	/// The horizontal advance of each rendered glyph, line by line in visual (left to right) order, which includes the kerning with the previous glyph and the extra character spacing (combining marks have an advance of 0)
+	/// 
+	/// Without the `shaping` feature there's usually one glyph per char (skipping line breaks, whitespace removed by wrapping, and invisible joiners), but with it ligatures and other substitutions can change the number of glyphs. Also, a soft hyphen that a line is broken at adds the advance of a visible `-`
+	pub advances: Vec<f32>,
+}
+
+
+
 /// Horizontal alignment
 #[derive(Copy, Clone)]
 pub enum HAlign {
@@ -116,18 +400,202 @@ pub enum HAlign {
 	Center,
 	/// Treats the 'x' value as the right edge
 	Right,
+	/// Treats the 'x' value as the edge that the text starts at (left for left-to-right paragraphs, right for right-to-left paragraphs)
+	Start,
+	/// Treats the 'x' value as the edge that the text ends at (right for left-to-right paragraphs, left for right-to-left paragraphs)
+	End,
 }
 
 impl HAlign {
-	pub(crate) fn get_offset(&self, width: f32) -> f32 {
-		match self {
-			Self::Left => 0.0,
-			Self::Center => width * -0.5,
-			Self::Right => -width,
+	pub(crate) fn get_offset(&self, width: f32, rtl: bool) -> f32 {
+		match (self, rtl) {
+			(Self::Left, _) | (Self::Start, false) | (Self::End, true) => 0.0,
+			(Self::Center, _) => width * -0.5,
+			(Self::Right, _) | (Self::Start, true) | (Self::End, false) => -width,
 		}
 	}
 }
 
+/// The physical order of a display's sub-pixels, which sub-pixel rendering has to match to avoid color fringing
+/// 
+/// The vertical orders are for displays whose sub-pixels are stacked top to bottom, which usually happens when a display is rotated into portrait mode
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum SubpixelOrder {
+	/// Horizontal, with red on the left (used by most displays)
+	#[default]
+	Rgb,
+	/// Horizontal, with blue on the left
+	Bgr,
+	/// Vertical, with red on top
+	Vrgb,
+	/// Vertical, with blue on top
+	Vbgr,
+}
+
+impl SubpixelOrder {
+	pub(crate) fn is_vertical(self) -> bool {
+		matches!(self, Self::Vrgb | Self::Vbgr)
+	}
+	pub(crate) fn is_bgr(self) -> bool {
+		matches!(self, Self::Bgr | Self::Vbgr)
+	}
+}
+
+
+
+/// How sub-pixel coverage is filtered (to reduce color fringing) and adjusted, see the presets for examples
+/// 
+/// Each glyph is cached per filter, so switching between filters every frame will keep re-rasterizing characters
+#[derive(Copy, Clone, Debug)]
+pub struct SubpixelFilter {
+	/// The weights of the filter along the axis that sub-pixels are split along, centered on each sub-pixel (these are normalized, so only their ratios matter)
+	pub weights: [f32; 5],
+	/// The weight of the neighboring pixels on the other axis, relative to a weight of 1.0 for the pixel itself (0.0 disables this filter)
+	pub cross_weight: f32,
+	/// The exponent applied to the filtered coverage, where values below 1.0 make text bolder and higher contrast, and values above 1.0 make it thinner
+	pub gamma: f32,
+}
+
+impl SubpixelFilter {
+	/// The filter that this crate has always used, which is a bit softer than FreeType's filters
+	pub const DEFAULT: Self = Self {
+		weights: [0.09526326, 0.55556049, 1.0, 0.55556049, 0.09526326],
+		cross_weight: 0.00504176,
+		gamma: SUBPIXEL_VALUE_POW,
+	};
+	/// The same weights as FreeType's `FT_LCD_FILTER_DEFAULT`, which is a good balance between sharpness and color fringing
+	pub const FREETYPE_DEFAULT: Self = Self {
+		weights: [0.03125, 0.30078125, 0.3359375, 0.30078125, 0.03125],
+		cross_weight: 0.0,
+		gamma: 1.0,
+	};
+	/// The same weights as FreeType's `FT_LCD_FILTER_LIGHT`, which is sharper but has more color fringing
+	pub const FREETYPE_LIGHT: Self = Self {
+		weights: [0.0, 0.33203125, 0.3359375, 0.33203125, 0.0],
+		cross_weight: 0.0,
+		gamma: 1.0,
+	};
+	/// An approximation of FreeType's `FT_LCD_FILTER_LEGACY` (which filters within each pixel instead of across sub-pixels), which is the sharpest and has the most color fringing
+	pub const FREETYPE_LEGACY: Self = Self {
+		weights: [0.07692308, 0.16666667, 0.6923077, 0.16666667, 0.07692308],
+		cross_weight: 0.0,
+		gamma: 1.0,
+	};
+	/// Creates a filter with custom weights and gamma (see the fields for what each does)
+	pub fn custom(weights: [f32; 5], cross_weight: f32, gamma: f32) -> Self {
+		Self {weights, cross_weight, gamma}
+	}
+	// floats can't be compared or hashed directly, so the filter is compared by its bits (which is fine since it's only used as part of a cache key)
+	fn to_bits(self) -> ([u32; 5], u32, u32) {
+		(self.weights.map(f32::to_bits), self.cross_weight.to_bits(), self.gamma.to_bits())
+	}
+}
+
+impl Default for SubpixelFilter {
+	fn default() -> Self {
+		Self::DEFAULT
+	}
+}
+
+impl PartialEq for SubpixelFilter {
+	fn eq(&self, other: &Self) -> bool {
+		self.to_bits() == other.to_bits()
+	}
+}
+
+impl Eq for SubpixelFilter {}
+
+impl std::hash::Hash for SubpixelFilter {
+	fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+		self.to_bits().hash(state);
+	}
+}
+
+
+
+/// Settings for blending text in linear light, see `TextRenderingSettings::gamma_correction`
+/// 
+/// Regular glyphs are blended by the gpu without knowing the background, so for them this adjusts the coverage based on how light the foreground is (which is exact for white or black text on the opposite color, and close otherwise). Because of that, regular glyphs are cached separately for a few levels of foreground brightness while this is enabled
+/// 
+/// NOTE: this isn't applied to `render_text_subpixel_blended()` or `render_text_sdf()`
+#[derive(Copy, Clone, Debug)]
+pub struct GammaCorrection {
+	/// The gamma used to convert colors to and from linear light (2.2 is close to sRGB)
+	pub gamma: f32,
+	/// How much to thicken text that is darker than its background (0.0 doesn't change it), since blending in linear light makes dark-on-light text look thinner
+	pub dark_text_contrast: f32,
+	/// How much to thicken text that is lighter than its background (0.0 doesn't change it)
+	pub light_text_contrast: f32,
+}
+
+impl GammaCorrection {
+	/// A slightly lower gamma than sRGB (which keeps light text from getting too bold), with extra contrast for dark text so that dark-on-light and light-on-dark text look about equally thick
+	pub const DEFAULT: Self = Self {
+		gamma: 1.8,
+		dark_text_contrast: 1.0,
+		light_text_contrast: 0.0,
+	};
+	#[allow(missing_docs)]
+	pub fn new(gamma: f32, dark_text_contrast: f32, light_text_contrast: f32) -> Self {
+		Self {gamma, dark_text_contrast, light_text_contrast}
+	}
+	pub(crate) fn linearize(self, value: u8) -> f32 {
+		(value as f32 / 255.0).powf(self.gamma)
+	}
+	pub(crate) fn delinearize(self, value: f32) -> u8 {
+		(value.clamp(0.0, 1.0).powf(1.0 / self.gamma) * 255.0).round() as u8
+	}
+	/// The relative luminance of the color (in linear light, from 0.0 to 1.0)
+	pub(crate) fn get_luminance(self, color: Color) -> f32 {
+		0.2126 * self.linearize(color.r) + 0.7152 * self.linearize(color.g) + 0.0722 * self.linearize(color.b)
+	}
+	pub(crate) fn apply_contrast(self, coverage: f32, is_dark_text: bool) -> f32 {
+		let contrast = if is_dark_text {self.dark_text_contrast} else {self.light_text_contrast};
+		coverage.powf(1.0 / (1.0 + contrast.max(0.0)))
+	}
+	/// Blends one channel of the foreground over the background in linear light
+	pub(crate) fn blend(self, background: u8, foreground: u8, coverage: f32, is_dark_text: bool) -> u8 {
+		let coverage = self.apply_contrast(coverage, is_dark_text);
+		self.delinearize(self.linearize(background) * (1.0 - coverage) + self.linearize(foreground) * coverage)
+	}
+	/// Adjusts coverage so that blending it in sRGB looks like blending in linear light, for a foreground with the given luminance
+	pub(crate) fn correct_coverage(self, coverage: f32, luminance: f32) -> f32 {
+		// in linear light, light text on a dark background gets brighter and dark text on a light background gets lighter, so the coverage is bent towards whichever one the foreground is closer to
+		let light = coverage.powf(1.0 / self.gamma);
+		let dark = 1.0 - (1.0 - coverage).powf(1.0 / self.gamma);
+		let coverage = dark + (light - dark) * luminance;
+		self.apply_contrast(coverage, luminance < 0.5)
+	}
+	fn to_bits(self) -> (u32, u32, u32) {
+		(self.gamma.to_bits(), self.dark_text_contrast.to_bits(), self.light_text_contrast.to_bits())
+	}
+}
+
+impl Default for GammaCorrection {
+	fn default() -> Self {
+		Self::DEFAULT
+	}
+}
+
+impl PartialEq for GammaCorrection {
+	fn eq(&self, other: &Self) -> bool {
+		self.to_bits() == other.to_bits()
+	}
+}
+
+impl Eq for GammaCorrection {}
+
+impl std::hash::Hash for GammaCorrection {
+	fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+		self.to_bits().hash(state);
+	}
+}
+
+// how regular glyphs' coverage is turned into alpha: `None` for the original curve, otherwise the gamma correction and the foreground's luminance (rounded to eighths, see `get_coverage_curve()`)
+pub(crate) type CoverageCurve = Option<(GammaCorrection, u8)>;
+
+
+
 /// Vertical alignment
 #[derive(Copy, Clone)]
 pub enum VAlign {
@@ -140,11 +608,12 @@ pub enum VAlign {
 }
 
 impl VAlign {
-	pub(crate) fn get_offset(&self, height: f32) -> f32 {
+	/// Gets the offset of the first line's baseline, where `lines_height` is the distance from the first line's baseline to the last line's baseline
+	pub(crate) fn get_offset(&self, height: f32, lines_height: f32) -> f32 {
 		match self {
 			Self::Top => height * TEXT_HEIGHT_MULT,
-			Self::Center => height * TEXT_HEIGHT_MULT * 0.5,
-			Self::Bottom => 0.0,
+			Self::Center => (height * TEXT_HEIGHT_MULT - lines_height) * 0.5,
+			Self::Bottom => -lines_height,
 		}
 	}
 }
@@ -0,0 +1,365 @@
+use crate::*;
+use ab_glyph::{Font, GlyphId, PxScale, ScaleFont};
+use sdl3::render::RenderTarget;
+use unicode_linebreak::BreakOpportunity;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_bidi::{Level, ParagraphBidiInfo};
+
+
+
+/// Measures text without drawing it or rasterizing any characters
+/// 
+/// NOTE: `render_text_subpixel()` converts the size to u32, so if you're measuring text for subpixel rendering you should use a whole number size
+pub fn measure_text<F: ThreadSafeFont, T: RenderTarget>(text: impl AsRef<str>, settings: &TextRenderingSettings<F, T>) -> TextMetrics {
+	settings.text_cache.measure_text_with(text, settings.size, settings.line_spacing, settings.max_width)
+}
+
+
+
+/// Text that has already been laid out (split into lines, converted to glyphs, and positioned), which can be drawn any number of times with `render_layout_regular()` or `render_layout_subpixel()`
+/// 
+/// This is useful for text that is drawn every frame but rarely changes, since the layout only has to be re-created when the text changes
+/// 
+/// NOTE: a layout is tied to the fonts of the `TextCache` that created it, so it should be re-created after switching fonts
+#[derive(Clone)]
+pub struct TextLayout {
+	pub(crate) text: LaidOutText,
+	pub(crate) size: f32,
+	pub(crate) ascent: f32,
+	pub(crate) descent: f32,
+	pub(crate) line_gap: f32,
+}
+
+impl TextLayout {
+	/// Lays out text using the size, line spacing, and wrapping width from `settings`
+	/// 
+	/// NOTE: `render_text_subpixel()` converts the size to u32, so layouts for sub-pixel rendering should use a whole number size
+	pub fn new<F: ThreadSafeFont, T: RenderTarget>(text: impl AsRef<str>, settings: &TextRenderingSettings<F, T>) -> Self {
+		Self::new_with(text, settings.size, settings.line_spacing, settings.max_width, settings.text_cache)
+	}
+	/// Same as `new()`, but without needing `TextRenderingSettings`
+	pub fn new_with<F: ThreadSafeFont, C>(text: impl AsRef<str>, size: f32, line_spacing: f32, max_width: Option<f32>, text_cache: &TextCache<F, C>) -> Self {
+		let font = text_cache.fonts[0].as_scaled(PxScale::from(size));
+		Self {
+			text: layout_text(text.as_ref(), size, line_spacing, max_width, &text_cache.fonts),
+			size,
+			ascent: font.ascent(),
+			descent: font.descent(),
+			line_gap: font.line_gap(),
+		}
+	}
+	/// The size that the text was laid out at
+	pub fn size(&self) -> f32 {
+		self.size
+	}
+	/// The width of the widest line
+	pub fn width(&self) -> f32 {
+		self.text.width
+	}
+	/// The total height of all lines (the same as `TextMetrics::height`)
+	pub fn height(&self) -> f32 {
+		self.text.lines_height() + self.font_height()
+	}
+	/// The number of lines, after wrapping
+	pub fn line_count(&self) -> usize {
+		self.text.lines.len()
+	}
+	/// Gets the full measurements of the text (the same as what `measure_text()` would give)
+	pub fn metrics(&self) -> TextMetrics {
+		TextMetrics {
+			width: self.text.width,
+			height: self.height(),
+			line_count: self.text.lines.len(),
+			ascent: self.ascent,
+			descent: self.descent,
+			line_gap: self.line_gap,
+			advances: self.text.glyphs().map(|glyph| glyph.advance).collect(),
+		}
+	}
+	/// The font's height at this size (`ascent - descent`), which is used for vertical alignment
+	pub(crate) fn font_height(&self) -> f32 {
+		self.ascent - self.descent
+	}
+}
+
+
+
+/// A char that has been converted to a glyph and positioned within its line
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct LaidOutGlyph {
+	pub(crate) c: char,
+	/// Which font in the `TextCache` this glyph is from (0 is the main font, anything higher is a fallback font)
+	pub(crate) font_index: usize,
+	pub(crate) id: GlyphId,
+	/// The index of the char within its line's text
+	pub(crate) byte_index: usize,
+	/// The x position of the glyph's origin, relative to the start of the line
+	pub(crate) x: f32,
+	/// The y offset of the glyph's origin from the line's baseline (only non-zero for shaped text)
+	pub(crate) y: f32,
+	/// The horizontal space this glyph takes up (including kerning with the previous glyph and extra spacing)
+	pub(crate) advance: f32,
+}
+
+/// A single line of positioned glyphs
+#[derive(Clone)]
+pub(crate) struct LaidOutLine {
+	pub(crate) glyphs: Vec<LaidOutGlyph>,
+	pub(crate) width: f32,
+	/// The y position of this line's baseline, relative to the first line's baseline
+	pub(crate) y: f32,
+	/// Whether the line's paragraph is right-to-left (which affects `HAlign::Start` and `HAlign::End`)
+	pub(crate) rtl: bool,
+}
+
+/// A block of one or more lines of positioned glyphs
+#[derive(Clone)]
+pub(crate) struct LaidOutText {
+	pub(crate) lines: Vec<LaidOutLine>,
+	/// The width of the widest line
+	pub(crate) width: f32,
+	/// The distance between the baselines of consecutive lines
+	pub(crate) line_advance: f32,
+}
+
+impl LaidOutText {
+	pub(crate) fn glyphs(&self) -> impl Iterator<Item = &LaidOutGlyph> {
+		self.lines.iter().flat_map(|line| &line.glyphs)
+	}
+	/// The distance from the first line's baseline to the last line's baseline
+	pub(crate) fn lines_height(&self) -> f32 {
+		self.line_advance * self.lines.len().saturating_sub(1) as f32
+	}
+}
+
+/// Splits text on hard line breaks (and wraps lines longer than `max_width`) and lays out each line
+/// 
+/// Line break opportunities are found using the Unicode line breaking algorithm (UAX #14), so wrapping works for CJK text, hyphens, soft hyphens, and non-breaking spaces. Each line is wrapped in logical order (even when shaping would output right-to-left text in visual order) and then reordered for display using the Unicode bidirectional algorithm (UAX #9)
+pub(crate) fn layout_text<F: ThreadSafeFont>(text: &str, size: f32, line_spacing: f32, max_width: Option<f32>, fonts: &[F]) -> LaidOutText {
+	let scaled_font = fonts[0].as_scaled(PxScale::from(size));
+	let line_advance = (scaled_font.height() + scaled_font.line_gap()) * line_spacing;
+	let mut lines = vec!();
+	let mut line_start = 0;
+	let mut allowed_breaks = vec!();
+	for (i, opportunity) in unicode_linebreak::linebreaks(text) {
+		if opportunity == BreakOpportunity::Allowed {
+			allowed_breaks.push(i - line_start);
+			continue;
+		}
+		let line_text = text[line_start .. i].trim_end_matches(is_hard_line_break);
+		let bidi_info = ParagraphBidiInfo::new(line_text, None);
+		let line = layout_line(line_text, size, fonts);
+		match max_width {
+			Some(max_width) if line.width > max_width => {
+				// shaping outputs right-to-left paragraphs in visual order, but wrapping needs the glyphs in logical order
+				let line = if bidi_info.has_rtl() {layout_logical_line(line_text, size, fonts)} else {line};
+				for wrapped_text in wrap_line(line_text, &line, &allowed_breaks, size, max_width) {
+					lines.push(layout_bidi_line(&wrapped_text, bidi_info.paragraph_level, size, fonts));
+				}
+			}
+			_ if bidi_info.has_rtl() => lines.push(layout_bidi_line(line_text, bidi_info.paragraph_level, size, fonts)),
+			_ => lines.push(line),
+		}
+		line_start = i;
+		allowed_breaks.clear();
+	}
+	if lines.is_empty() || text.ends_with(is_hard_line_break) {
+		lines.push(layout_line("", size, fonts));
+	}
+	let mut width: f32 = 0.0;
+	for (i, line) in lines.iter_mut().enumerate() {
+		line.y = line_advance * i as f32;
+		width = width.max(line.width);
+	}
+	LaidOutText {lines, width, line_advance}
+}
+
+/// Splits an already laid out line into pieces that each fit within `max_width`, breaking at the given break opportunities (byte indices) when possible and between clusters otherwise (so combining marks are never separated from their base)
+/// 
+/// Trailing spaces are allowed to hang past `max_width`, and a soft hyphen that a line is broken at is turned into a visible hyphen
+fn wrap_line(text: &str, line: &LaidOutLine, allowed_breaks: &[usize], size: f32, max_width: f32) -> Vec<String> {
+	let glyphs = &line.glyphs;
+	let mut output = vec!();
+	let mut line_start = (0, 0); // (byte index, glyph index)
+	let mut last_break = None;
+	let mut last_cluster_start = None;
+	let mut i = 0;
+	while i < glyphs.len() {
+		let glyph = &glyphs[i];
+		if glyph.byte_index > line_start.0 && allowed_breaks.contains(&glyph.byte_index) {
+			last_break = Some((glyph.byte_index, i));
+		}
+		// marks have no advance, so only glyphs with an advance start a new cluster
+		if glyph.advance != 0.0 && i > line_start.1 {
+			last_cluster_start = Some((glyph.byte_index, i));
+		}
+		if is_breaking_space(glyph.c) {
+			i += 1;
+			continue;
+		}
+		let width = glyph.x + glyph.advance - size * EXTRA_CHAR_SPACING - glyphs[line_start.1].x;
+		if width > max_width && let Some(break_point) = last_break.or(last_cluster_start) {
+			output.push(finish_wrapped_line(&text[line_start.0 .. break_point.0]));
+			line_start = break_point;
+			last_break = None;
+			last_cluster_start = None;
+			continue;
+		}
+		i += 1;
+	}
+	output.push(finish_wrapped_line(&text[line_start.0 ..]));
+	output
+}
+
+fn finish_wrapped_line(text: &str) -> String {
+	let text = text.trim_end_matches(is_breaking_space);
+	match text.strip_suffix(SOFT_HYPHEN) {
+		Some(text) => format!("{text}-"),
+		None => text.to_string(),
+	}
+}
+
+pub(crate) const SOFT_HYPHEN: char = '\u{AD}';
+
+fn is_hard_line_break(c: char) -> bool {
+	matches!(c, '\n' | '\r' | '\u{0B}' | '\u{0C}' | '\u{85}' | '\u{2028}' | '\u{2029}')
+}
+
+/// Whitespace that lines can be broken at (so not including non-breaking spaces)
+fn is_breaking_space(c: char) -> bool {
+	c.is_whitespace() && !matches!(c, '\u{A0}' | '\u{2007}' | '\u{202F}')
+}
+
+/// Lays out a line in visual order, using the Unicode bidirectional algorithm (UAX #9) to reorder right-to-left runs
+fn layout_bidi_line<F: ThreadSafeFont>(text: &str, base_level: Level, size: f32, fonts: &[F]) -> LaidOutLine {
+	let bidi_info = ParagraphBidiInfo::new(text, Some(base_level));
+	if !bidi_info.has_rtl() {
+		let mut line = layout_line(text, size, fonts);
+		line.rtl = base_level.is_rtl();
+		return line;
+	}
+	let (levels, runs) = bidi_info.visual_runs(0 .. text.len());
+	let mut glyphs = Vec::with_capacity(text.len());
+	let mut x = 0.0;
+	for run in runs {
+		let run_line = layout_run(&text[run.clone()], levels[run.start].is_rtl(), size, fonts);
+		if run_line.glyphs.is_empty() {continue;}
+		glyphs.extend(run_line.glyphs.into_iter().map(|glyph| LaidOutGlyph {x: glyph.x + x, byte_index: glyph.byte_index + run.start, ..glyph}));
+		x += run_line.width + size * EXTRA_CHAR_SPACING;
+	}
+	let width = if glyphs.is_empty() {0.0} else {x - size * EXTRA_CHAR_SPACING};
+	LaidOutLine {glyphs, width, y: 0.0, rtl: base_level.is_rtl()}
+}
+
+/// Lays out text that all has the same direction
+fn layout_run<F: ThreadSafeFont>(text: &str, rtl: bool, size: f32, fonts: &[F]) -> LaidOutLine {
+	#[cfg(feature = "shaping")]
+	if let Some(line) = shape_line(text, Some(rtl), size, fonts) {return line;}
+	let mut line = layout_line_unshaped(text, size, fonts);
+	if rtl {
+		// reverse the glyph positions, while keeping combining marks attached to their base glyph
+		// NOTE: without shaping, mirrored chars (like brackets) aren't flipped
+		let total_width = line.width + size * EXTRA_CHAR_SPACING;
+		let mut base_shift = 0.0;
+		for glyph in &mut line.glyphs {
+			if glyph.advance == 0.0 {
+				glyph.x += base_shift;
+				continue;
+			}
+			let new_x = total_width - glyph.x - glyph.advance;
+			base_shift = new_x - glyph.x;
+			glyph.x = new_x;
+		}
+		line.glyphs.reverse();
+	}
+	line
+}
+
+/// Converts chars to glyphs and positions them in logical order (this is the shared spacing logic for measuring and all rendering)
+pub(crate) fn layout_line<F: ThreadSafeFont>(text: &str, size: f32, fonts: &[F]) -> LaidOutLine {
+	#[cfg(feature = "shaping")]
+	if let Some(line) = shape_line(text, None, size, fonts) {return line;}
+	layout_line_unshaped(text, size, fonts)
+}
+
+/// Same as `layout_line()`, but the glyphs are always in logical order (which is only different for right-to-left text with the `shaping` feature)
+fn layout_logical_line<F: ThreadSafeFont>(text: &str, size: f32, fonts: &[F]) -> LaidOutLine {
+	#[cfg(feature = "shaping")]
+	if let Some(line) = shape_line(text, Some(false), size, fonts) {return line;}
+	layout_line_unshaped(text, size, fonts)
+}
+
+/// Text is processed as extended grapheme clusters, so combining marks are placed over their base char instead of taking up their own space
+fn layout_line_unshaped<F: ThreadSafeFont>(text: &str, size: f32, fonts: &[F]) -> LaidOutLine {
+	let scaled_fonts = fonts.iter().map(|font| font.as_scaled(PxScale::from(size))).collect::<Vec<_>>();
+	let mut glyphs = Vec::with_capacity(text.len());
+	let mut x = 0.0;
+	let mut prev_glyph = None; // (font_index, id)
+	for (cluster_index, cluster) in text.grapheme_indices(true) {
+		let mut base = None; // (font_index, x, h_advance) of the cluster's base glyph
+		let mut after_zwj = false;
+		for (char_index, c) in cluster.char_indices() {
+			let byte_index = cluster_index + char_index;
+			if c == SOFT_HYPHEN {continue;} // only visible when a line is broken at it
+			if c == ZERO_WIDTH_JOINER {after_zwj = true; continue;}
+			if is_variation_selector(c) {continue;}
+			// joined sequences (like family emojis) can't be combined without text shaping, so only the first part is shown
+			if after_zwj {after_zwj = false; continue;}
+			let preferred_font = base.map(|(font_index, _x, _advance)| font_index).unwrap_or(0);
+			let font_index = find_font_for_char(c, preferred_font, fonts);
+			let font = &scaled_fonts[font_index];
+			let id = font.glyph_id(c);
+			let h_advance = font.h_advance(id);
+			
+			// combining marks
+			if let Some((_font_index, base_x, base_advance)) = base && (h_advance == 0.0 || is_combining_mark(c)) {
+				// zero-width marks are designed to be drawn over the previous glyph, while marks with their own width are centered over the base glyph
+				let mark_x = if h_advance == 0.0 {base_x + base_advance} else {base_x + (base_advance - h_advance) * 0.5};
+				glyphs.push(LaidOutGlyph {c, font_index, id, byte_index, x: mark_x, y: 0.0, advance: 0.0});
+				continue;
+			}
+			
+			let mut advance = 0.0;
+			if let Some((prev_font_index, prev_id)) = prev_glyph && prev_font_index == font_index {advance += font.kern(prev_id, id);}
+			let glyph_x = x + advance;
+			advance += h_advance;
+			advance += size * EXTRA_CHAR_SPACING;
+			if c.is_whitespace() {advance += size * EXTRA_WHITESPACE_SPACING;}
+			glyphs.push(LaidOutGlyph {c, font_index, id, byte_index, x: glyph_x, y: 0.0, advance});
+			x += advance;
+			prev_glyph = Some((font_index, id));
+			base = Some((font_index, glyph_x, h_advance));
+		}
+	}
+	let width = if glyphs.is_empty() {0.0} else {x - size * EXTRA_CHAR_SPACING};
+	LaidOutLine {glyphs, width, y: 0.0, rtl: false}
+}
+
+/// Splits an x position into a whole pixel and the index of the nearest of the `SUBPIXEL_POSITIONS` offsets within that pixel
+pub(crate) fn split_subpixel_position(x: f32) -> (f32, u32) {
+	let pixel = x.floor();
+	let phase = ((x - pixel) * SUBPIXEL_POSITIONS as f32).round() as u32;
+	if phase == SUBPIXEL_POSITIONS {(pixel + 1.0, 0)} else {(pixel, phase)}
+}
+
+/// The offset (in pixels) that a glyph is rasterized at for the given phase from `split_subpixel_position()`
+pub(crate) fn get_phase_offset(phase: u32) -> f32 {
+	phase as f32 / SUBPIXEL_POSITIONS as f32
+}
+
+/// Finds the first font that has a glyph for the char, checking `preferred_font` first (returns 0 if no font has the char, so that the primary font's "missing glyph" is shown)
+pub(crate) fn find_font_for_char<F: Font>(c: char, preferred_font: usize, fonts: &[F]) -> usize {
+	if fonts[preferred_font].glyph_id(c).0 != 0 {return preferred_font;}
+	fonts.iter().position(|font| font.glyph_id(c).0 != 0).unwrap_or(0)
+}
+
+const ZERO_WIDTH_JOINER: char = '\u{200D}';
+
+fn is_variation_selector(c: char) -> bool {
+	matches!(c, '\u{FE00}'..='\u{FE0F}' | '\u{E0100}'..='\u{E01EF}')
+}
+
+/// Checks for the combining diacritical mark blocks (this isn't every combining mark, but any zero-width char after a base char is also treated as a mark)
+fn is_combining_mark(c: char) -> bool {
+	matches!(c, '\u{0300}'..='\u{036F}' | '\u{1AB0}'..='\u{1AFF}' | '\u{1DC0}'..='\u{1DFF}' | '\u{20D0}'..='\u{20FF}' | '\u{FE20}'..='\u{FE2F}')
+}
@@ -6,10 +6,16 @@
 //! 
 //! **Quick api overview:**
 //! 
-//! - `TextCache` - holds all the long-lived data that this crate needs for rendering text, excluding the canvas and texture creator
+//! - `TextCache` - holds all the long-lived data that this crate needs for rendering text (including the font and any fallback fonts), excluding the canvas and texture creator
 //! - `TextRenderingSettings` - holds most of the arguments for the text rendering functions
 //! - `render_text_regular()` - renders text without subpixel rendering
 //! - `render_text_subpixel()` - renders text with subpixel rendering
+//! - `render_text_subpixel_blended()` - renders text with subpixel rendering over any background (images, gradients, etc)
+//! - `render_text_sdf()` - renders text from signed distance fields, for text that is resized often
+//! - `measure_text()` - measures text without rendering it
+//! - `TextLayout` / `render_layout_regular()` / `render_layout_subpixel()` - lays out text once so it can be drawn many times
+//! - `rasterize_text_regular()` / `rasterize_text_subpixel()` - rasterizes text into a pixel buffer without an sdl3 renderer
+//! - `PrerenderedText` - text that is baked into a single texture, for static labels
 //! 
 //! ### Example Code:
 //! 
@@ -26,7 +32,7 @@
 //! 	canvas.clear();
 //! 	
 //! 	let size: u32 = 25;
-//! 	let (mut x, mut y): (i32, i32) = (50, 50);
+//! 	let (mut x, mut y): (f32, f32) = (50.0, 50.0);
 //! 	let foreground = Color::RGB(30, 30, 30);
 //! 	let background = Color::RGB(255, 255, 255);
 //! 	
@@ -39,9 +45,9 @@
 //! 	);
 //! 	
 //! 	render_text_subpixel("Example text", x, y, &mut text_rendering_settings)?;
-//! 	y += size as i32;
+//! 	y += size as f32;
 //! 	render_text_subpixel("More example text", x, y, &mut text_rendering_settings)?;
-//! 	y += size as i32;
+//! 	y += size as f32;
 //! 	
 //! 	canvas.present();
 //! 	Ok(())
@@ -52,7 +58,7 @@
 //! 
 //! ### Known Limitations:
 //! 
-//! - The subpixel rendering cannot blend into a background, and must have the background color supplied so that it can pre-blend the text onto a single color. This is because of subpixel rendering's per-channel mixing, which cannot be done after initial rasterization without custom shaders, which sdl3's renderer api doesn't support.
+//! - The subpixel rendering (`render_text_subpixel()`) cannot blend into a background, and must have the background color supplied so that it can pre-blend the text onto a single color. This is because of subpixel rendering's per-channel mixing, which cannot be done after initial rasterization without custom shaders, which sdl3's renderer api doesn't support. `render_text_subpixel_blended()` gets around this by drawing each string twice with custom blend modes, but that isn't supported by sdl3's software renderer.
 //! - The subpixel rendering takes an integer size, which makes resizing look slightly strange. This is an intentional choice to cut down significantly on the number of textures to rasterize and cache.
 
 
@@ -60,8 +66,6 @@
 #![warn(missing_docs)]
 #![allow(clippy::tabs_in_doc_comments)]
 
-#![feature(array_windows)]
-
 
 
 /// Functions for non-sub-pixel rendering (a bit faster and easier to use, but looks a bit pixelated)
@@ -70,9 +74,35 @@ pub use regular::*;
 /// Functions for sub-pixel rendering (limited and a bit slower but looks really nice)
 pub mod subpixel;
 pub use subpixel::*;
+/// Functions for rendering from signed distance fields (for text whose size changes often)
+pub mod sdf;
+pub use sdf::*;
 /// All data types for this crate
 pub mod data;
 pub use data::*;
+/// Functions for measuring and laying out text
+pub mod layout;
+pub use layout::*;
+/// Functions for rasterizing text into pixel buffers on the cpu (without an sdl3 renderer)
+pub mod raster;
+pub use raster::*;
+/// Text that is baked into a single texture, for drawing static text cheaply
+pub mod prerendered;
+pub use prerendered::*;
+/// Rasterizing and caching characters ahead of time
+pub mod prewarm;
+pub use prewarm::*;
+/// Text shaping with rustybuzz (only used when the `shaping` feature is enabled)
+#[cfg(feature = "shaping")]
+pub(crate) mod shaping;
+#[cfg(feature = "shaping")]
+pub(crate) use shaping::*;
+/// Packing of rasterized glyphs into shared textures
+pub(crate) mod atlas;
+pub(crate) use atlas::*;
+/// Batching of glyph quads into `render_geometry()` calls
+pub(crate) mod batch;
+pub(crate) use batch::*;
 
 
 
@@ -81,3 +111,4 @@ pub(crate) const EXTRA_WHITESPACE_SPACING: f32 = 0.045; // Note: this is added o
 pub(crate) const REGULAR_VALUE_POW: f32 = 0.7; // affects how dark the edges are
 pub(crate) const SUBPIXEL_VALUE_POW: f32 = 0.9;
 pub(crate) const TEXT_HEIGHT_MULT: f32 = 0.63; // This is the ratio of actual rendered height to given text size
+pub(crate) const SUBPIXEL_POSITIONS: u32 = 4; // The number of horizontal offsets within a pixel that glyphs are rasterized at
@@ -0,0 +1,77 @@
+use crate::*;
+use sdl3::{pixels::PixelFormat, render::{BlendMode, Canvas, FRect, RenderTarget, Texture}, sys::pixels::SDL_PixelFormat};
+
+
+
+/// Text that has been baked into a single texture, so it can be drawn with one `copy()` and without re-doing any layout
+/// 
+/// This is meant for text that never (or rarely) changes, like menu labels. The alignment and colors are taken from the `TextRenderingSettings` that it was created with
+pub struct PrerenderedText<'a> {
+	texture: Option<Texture<'a>>, // `None` when the text has nothing to draw
+	width: u32,
+	height: u32,
+	// the offset from the (x, y) given to `draw()` to the image's top-left corner
+	x_offset: f32,
+	y_offset: f32,
+	subpixel: bool,
+	metrics: TextMetrics,
+}
+
+impl<'a> PrerenderedText<'a> {
+	/// Bakes text into a texture without sub-pixel rendering
+	pub fn new_regular<F: ThreadSafeFont, T: RenderTarget>(text: impl AsRef<str>, settings: &TextRenderingSettings<'_, 'a, F, T>) -> Result<Self, RenderTextError> {
+		let rasterized_text = rasterize_text_regular(text, settings.size, settings.line_spacing, settings.max_width, settings.h_align, settings.foreground, settings.gamma_correction, settings.text_cache);
+		Self::from_rasterized_text(rasterized_text, settings, false)
+	}
+	/// Bakes text into a texture with sub-pixel rendering (which uses `settings.background`, the same as `render_text_subpixel()`)
+	pub fn new_subpixel<F: ThreadSafeFont, T: RenderTarget>(text: impl AsRef<str>, settings: &TextRenderingSettings<'_, 'a, F, T>) -> Result<Self, RenderTextError> {
+		let rasterized_text = rasterize_text_subpixel(text, settings.size as u32, settings.line_spacing, settings.max_width, settings.h_align, settings.foreground, settings.background, settings.subpixel_order, settings.subpixel_filter, settings.gamma_correction, settings.text_cache);
+		Self::from_rasterized_text(rasterized_text, settings, true)
+	}
+	fn from_rasterized_text<F: ThreadSafeFont, T: RenderTarget>(rasterized_text: RasterizedText, settings: &TextRenderingSettings<'_, 'a, F, T>, subpixel: bool) -> Result<Self, RenderTextError> {
+		let RasterizedText {pixels, width, height, anchor_x, baseline, metrics} = rasterized_text;
+		let texture = if width > 0 && height > 0 {
+			let mut texture = settings.texture_creator.create_texture_static(Some(unsafe {PixelFormat::from_ll(SDL_PixelFormat::RGBA32)}), width, height)?;
+			texture.update(None, &pixels, width as usize * 4)?;
+			texture.set_blend_mode(BlendMode::Blend);
+			Some(texture)
+		} else {
+			None
+		};
+		let font_height = metrics.ascent - metrics.descent;
+		let lines_height = metrics.height - font_height;
+		Ok(Self {
+			texture,
+			width,
+			height,
+			// the lines were already aligned around the anchor when they were rasterized
+			x_offset: -anchor_x,
+			y_offset: settings.v_align.get_offset(font_height, lines_height) - baseline,
+			subpixel,
+			metrics,
+		})
+	}
+	/// Draws the text with the same alignment as `render_text_regular()` / `render_text_subpixel()`
+	/// 
+	/// NOTE: the canvas must be the one whose texture creator was used to create this
+	pub fn draw<T: RenderTarget>(&self, x: f32, y: f32, canvas: &mut Canvas<T>) -> Result<(), RenderTextError> {
+		let Some(texture) = &self.texture else {return Ok(());};
+		let (x, y) = (x + self.x_offset, y + self.y_offset);
+		// the texture is drawn 1:1, so snap it to whole pixels to keep it from being blurred
+		let (x, y) = if self.subpixel {(x.floor(), y.floor())} else {(x.round(), y.round())};
+		canvas.copy(texture, None, FRect::new(x, y, self.width as f32, self.height as f32))?;
+		Ok(())
+	}
+	/// The measurements of the text
+	pub fn metrics(&self) -> &TextMetrics {
+		&self.metrics
+	}
+	/// The width of the baked texture (which can be slightly larger than `metrics().width`, since some characters extend past the edges of the text)
+	pub fn width(&self) -> u32 {
+		self.width
+	}
+	/// The height of the baked texture
+	pub fn height(&self) -> u32 {
+		self.height
+	}
+}
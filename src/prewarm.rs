@@ -0,0 +1,130 @@
+use crate::*;
+use std::time::{Duration, Instant};
+use ab_glyph::GlyphId;
+use sdl3::{pixels::Color, render::TextureCreator};
+
+
+
+/// Common sets of chars, for use with `TextCache::prewarm_regular()`, `TextCache::prewarm_subpixel()`, and `PrewarmJob`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Charset {
+	/// The digits 0-9
+	Digits,
+	/// All printable ASCII chars (including space)
+	Ascii,
+	/// All printable ASCII and Latin-1 Supplement chars (which covers most western european languages)
+	Latin1,
+	/// All printable Latin-1 chars plus the Latin Extended-A block (which covers most other european languages that use the latin alphabet)
+	LatinExtended,
+}
+
+impl Charset {
+	/// Iterates over every char in the set
+	pub fn chars(self) -> impl Iterator<Item = char> {
+		let ranges: &[(char, char)] = match self {
+			Self::Digits => &[('0', '9')],
+			Self::Ascii => &[(' ', '~')],
+			Self::Latin1 => &[(' ', '~'), ('\u{A0}', '\u{FF}')],
+			Self::LatinExtended => &[(' ', '~'), ('\u{A0}', '\u{17F}')],
+		};
+		ranges.iter().flat_map(|(start, end)| *start ..= *end)
+	}
+}
+
+
+
+/// Chars that are waiting to be rasterized and cached over several frames, see `TextCache::run_prewarm_job()`
+pub struct PrewarmJob {
+	chars: Vec<char>,
+	next_index: usize,
+	mode: PrewarmMode,
+}
+
+#[derive(Copy, Clone)]
+enum PrewarmMode {
+	Regular {size: f32, curve: CoverageCurve},
+	Subpixel {size: u32, foreground: Color, background: Color, order: SubpixelOrder, filter: SubpixelFilter, gamma_correction: Option<GammaCorrection>},
+}
+
+impl PrewarmJob {
+	/// Creates a job that prewarms the chars for `render_text_regular()`
+	/// 
+	/// `foreground` is only used when `gamma_correction` is `Some`, see `TextCache::prewarm_regular()`
+	pub fn new_regular(chars: impl IntoIterator<Item = char>, size: f32, foreground: impl Into<Color>, gamma_correction: Option<GammaCorrection>) -> Self {
+		Self {
+			chars: chars.into_iter().collect(),
+			next_index: 0,
+			mode: PrewarmMode::Regular {size, curve: get_coverage_curve(gamma_correction, foreground.into())},
+		}
+	}
+	/// Creates a job that prewarms the chars for `render_text_subpixel()`
+	#[allow(clippy::too_many_arguments)]
+	pub fn new_subpixel(chars: impl IntoIterator<Item = char>, size: u32, foreground: impl Into<Color>, background: impl Into<Color>, order: SubpixelOrder, filter: SubpixelFilter, gamma_correction: Option<GammaCorrection>) -> Self {
+		Self {
+			chars: chars.into_iter().collect(),
+			next_index: 0,
+			mode: PrewarmMode::Subpixel {size, foreground: foreground.into(), background: background.into(), order, filter, gamma_correction},
+		}
+	}
+	/// Whether every char has been prewarmed
+	pub fn is_done(&self) -> bool {
+		self.next_index >= self.chars.len()
+	}
+	/// The number of chars that haven't been prewarmed yet
+	pub fn remaining(&self) -> usize {
+		self.chars.len() - self.next_index
+	}
+}
+
+
+
+impl<'a, F: ThreadSafeFont, C> TextCache<'a, F, C> {
+	/// Rasterizes and caches chars for `render_text_regular()` ahead of time, so that the first frame that uses them doesn't have to (for example, `Charset::Ascii.chars()` can be passed as `chars`)
+	/// 
+	/// Regular glyphs are cached independently of color, but each is cached at a few sizes (nearby sizes share the same glyphs)
+	/// 
+	/// `gamma_correction` should match `TextRenderingSettings::gamma_correction`, and when it's `Some`, glyphs are also cached per foreground brightness so `foreground` should match `TextRenderingSettings::foreground` (otherwise `foreground` is ignored)
+	/// 
+	/// NOTE: with the `shaping` feature, ligatures and other substituted glyphs still have to be rasterized when they're first drawn
+	pub fn prewarm_regular(&mut self, chars: impl IntoIterator<Item = char>, size: f32, foreground: impl Into<Color>, gamma_correction: Option<GammaCorrection>, texture_creator: &'a TextureCreator<C>) -> Result<(), RenderTextError> {
+		let glyphs = self.chars_to_glyphs(chars);
+		cache_glyphs_regular(glyphs.into_iter(), get_size_bucket(size), get_coverage_curve(gamma_correction, foreground.into()), self, texture_creator)
+	}
+	/// Rasterizes and caches chars for `render_text_subpixel()` ahead of time, so that the first frame that uses them doesn't have to
+	/// 
+	/// Sub-pixel glyphs are cached per size, colors, sub-pixel order, filter, and gamma correction, so this has to be called for each combination that will be drawn (`render_text_subpixel_blended()` uses white on black)
+	#[allow(clippy::too_many_arguments)]
+	pub fn prewarm_subpixel(&mut self, chars: impl IntoIterator<Item = char>, size: u32, foreground: impl Into<Color>, background: impl Into<Color>, order: SubpixelOrder, filter: SubpixelFilter, gamma_correction: Option<GammaCorrection>, texture_creator: &'a TextureCreator<C>) -> Result<(), RenderTextError> {
+		let glyphs = self.chars_to_glyphs(chars);
+		cache_glyphs_subpixel(glyphs.into_iter(), size, foreground.into(), background.into(), order, filter, gamma_correction, self, texture_creator)
+	}
+	/// Prewarms chars from the job until either the job is done or `time_budget` has run out (at least a few chars are always prewarmed), and returns whether the job is done
+	/// 
+	/// This is meant to be called once per frame so that prewarming a large set of chars doesn't cause a single long frame
+	pub fn run_prewarm_job(&mut self, job: &mut PrewarmJob, time_budget: Duration, texture_creator: &'a TextureCreator<C>) -> Result<bool, RenderTextError> {
+		let start = Instant::now();
+		// enough chars to keep every thread busy
+		let chunk_size = rayon::current_num_threads() * 4;
+		while !job.is_done() {
+			let chunk_end = (job.next_index + chunk_size).min(job.chars.len());
+			let glyphs = self.chars_to_glyphs(job.chars[job.next_index .. chunk_end].iter().copied());
+			match job.mode {
+				PrewarmMode::Regular {size, curve} => cache_glyphs_regular(glyphs.into_iter(), get_size_bucket(size), curve, self, texture_creator)?,
+				PrewarmMode::Subpixel {size, foreground, background, order, filter, gamma_correction} => cache_glyphs_subpixel(glyphs.into_iter(), size, foreground, background, order, filter, gamma_correction, self, texture_creator)?,
+			}
+			job.next_index = chunk_end;
+			if start.elapsed() >= time_budget {break;}
+		}
+		Ok(job.is_done())
+	}
+	/// Picks the font for each char the same way that text layout does, and includes every sub-pixel position (since chars can be drawn at any x position)
+	fn chars_to_glyphs(&self, chars: impl IntoIterator<Item = char>) -> Vec<(usize, GlyphId, u32)> {
+		chars.into_iter()
+			.flat_map(|c| {
+				let font_index = find_font_for_char(c, 0, &self.fonts);
+				let id = self.fonts[font_index].glyph_id(c);
+				(0..SUBPIXEL_POSITIONS).map(move |phase| (font_index, id, phase))
+			})
+			.collect()
+	}
+}
@@ -0,0 +1,162 @@
+use crate::*;
+use std::{collections::{HashMap, HashSet}, sync::Mutex};
+use ab_glyph::{GlyphId, PxScale};
+use sdl3::{pixels::{Color, PixelFormat}, surface::Surface, sys::pixels::SDL_PixelFormat};
+
+
+
+/// Text that has been rasterized into a cpu-side pixel buffer, as returned by `rasterize_text_regular()` and `rasterize_text_subpixel()`
+#[derive(Clone, Debug)]
+pub struct RasterizedText {
+	/// The pixel data in RGBA byte order (4 bytes per pixel, with no padding between rows)
+	pub pixels: Vec<u8>,
+	#[allow(missing_docs)]
+	pub width: u32,
+	#[allow(missing_docs)]
+	pub height: u32,
+	/// The x position within the image that the lines are aligned to, which is where the `x` given to `render_text_regular()` would be (for example, this is the right edge of the text with `HAlign::Right`)
+	pub anchor_x: f32,
+	/// The y position of the first line's baseline within the image
+	pub baseline: f32,
+	/// The measurements of the text (the same as what `measure_text()` would give)
+	pub metrics: TextMetrics,
+}
+
+impl RasterizedText {
+	/// Copies the pixels into a new sdl3 surface (which uses the `RGBA32` pixel format)
+	pub fn to_surface(&self) -> Result<Surface<'static>, RenderTextError> {
+		let mut surface = Surface::new(self.width, self.height, unsafe {PixelFormat::from_ll(SDL_PixelFormat::RGBA32)})?;
+		let pitch = surface.pitch() as usize;
+		let row_len = self.width as usize * 4;
+		if row_len == 0 {return Ok(surface);}
+		surface.with_lock_mut(|data| {
+			for (y, row) in self.pixels.chunks_exact(row_len).enumerate() {
+				data[y * pitch .. y * pitch + row_len].copy_from_slice(row);
+			}
+		});
+		Ok(surface)
+	}
+}
+
+
+
+/// Lays out and rasterizes text into a pixel buffer without using an sdl3 renderer (or the `TextCache`'s textures), which is useful for generating textures at load time, exporting images, and testing on machines without a gpu
+/// 
+/// The background of the image is transparent, and each line is aligned to `RasterizedText::anchor_x` using `h_align`, the same way that `render_text_regular()` aligns each line to its `x` (so `HAlign::Start` and `HAlign::End` follow each paragraph's direction)
+#[allow(clippy::too_many_arguments)]
+pub fn rasterize_text_regular<F: ThreadSafeFont, C>(text: impl AsRef<str>, size: f32, line_spacing: f32, max_width: Option<f32>, h_align: HAlign, foreground: impl Into<Color>, gamma_correction: Option<GammaCorrection>, text_cache: &TextCache<F, C>) -> RasterizedText {
+	let (text, foreground) = (text.as_ref(), foreground.into());
+	let layout = TextLayout::new_with(text, size, line_spacing, max_width, text_cache);
+	let fonts = text_cache.fonts.iter().map(|font| font.as_scaled(PxScale::from(size))).collect::<Vec<_>>();
+	// unlike `render_text_regular()`, glyphs are rasterized at the final size since they don't need to be reused at other sizes
+	let curve = get_coverage_curve(gamma_correction, foreground);
+	let glyphs = rasterize_unique_glyphs(&layout.text, |font_index, id| rasterize_glyph_regular(id.with_scale(size), curve, &fonts[font_index]));
+	let background = Color::RGBA(foreground.r, foreground.g, foreground.b, 0);
+	let mut rasterized_text = compose_text(&layout.text, layout.metrics(), h_align, &glyphs, background, false);
+	// the glyphs only hold coverage, so the foreground's alpha is applied afterwards
+	for pixel in rasterized_text.pixels.chunks_exact_mut(4) {
+		pixel[3] = (pixel[3] as u16 * foreground.a as u16 / 255) as u8;
+	}
+	rasterized_text
+}
+
+/// Same as `rasterize_text_regular()`, but with sub-pixel rendering
+/// 
+/// The image is filled with `background`, and like `render_text_subpixel()` the characters are pre-blended onto it
+#[allow(clippy::too_many_arguments)]
+pub fn rasterize_text_subpixel<F: ThreadSafeFont, C>(text: impl AsRef<str>, size: u32, line_spacing: f32, max_width: Option<f32>, h_align: HAlign, foreground: impl Into<Color>, background: impl Into<Color>, order: SubpixelOrder, filter: SubpixelFilter, gamma_correction: Option<GammaCorrection>, text_cache: &TextCache<F, C>) -> RasterizedText {
+	let (text, foreground, background) = (text.as_ref(), foreground.into(), background.into());
+	let layout = TextLayout::new_with(text, size as f32, line_spacing, max_width, text_cache);
+	let fonts = get_subpixel_fonts(&text_cache.fonts, size, order);
+	let glyphs = rasterize_unique_glyphs(&layout.text, |font_index, id| {
+		let font = &fonts[font_index];
+		rasterize_glyph_subpixel(id.with_scale(font.scale), foreground, background, order, filter, gamma_correction, font)
+	});
+	compose_text(&layout.text, layout.metrics(), h_align, &glyphs, background, true)
+}
+
+
+
+type RasterizedGlyph = (GlyphId, Vec<u8>, u32, u32, f32, f32);
+
+/// Rasterizes each distinct glyph in the layout once, in parallel
+fn rasterize_unique_glyphs(layout: &LaidOutText, rasterize: impl Fn(usize, GlyphId) -> Option<RasterizedGlyph> + Sync) -> HashMap<(usize, GlyphId), RasterizedGlyph> {
+	let mut seen = HashSet::new();
+	let output = Mutex::new(HashMap::new());
+	rayon::scope(|s| {
+		for glyph in layout.glyphs() {
+			let key = (glyph.font_index, glyph.id);
+			if !seen.insert(key) {continue;}
+			let (output, rasterize) = (&output, &rasterize);
+			s.spawn(move |_s| {
+				if let Some(data) = rasterize(key.0, key.1) {
+					output.lock().unwrap().insert(key, data);
+				}
+			});
+		}
+	});
+	output.into_inner().unwrap()
+}
+
+/// Places the rasterized glyphs into one image that fits every line's box and every glyph
+/// 
+/// Regular glyphs are alpha-blended, and sub-pixel glyphs (which are already blended onto the background) keep whichever value is furthest from the background for each channel, so that overlapping glyphs don't cover each other with background
+fn compose_text(layout: &LaidOutText, metrics: TextMetrics, h_align: HAlign, glyphs: &HashMap<(usize, GlyphId), RasterizedGlyph>, background: Color, subpixel: bool) -> RasterizedText {
+	
+	// find the position of each glyph relative to the anchor and the first baseline, where each line is aligned on its own like in `render_layout_regular()`
+	let mut placed_glyphs = vec!();
+	let (mut min_x, mut min_y) = (0, (-metrics.ascent).floor() as i32);
+	let (mut max_x, mut max_y) = (0, (layout.lines_height() - metrics.descent).ceil() as i32);
+	for line in &layout.lines {
+		let line_x = h_align.get_offset(line.width, line.rtl);
+		min_x = min_x.min(line_x.floor() as i32);
+		max_x = max_x.max((line_x + line.width).ceil() as i32);
+		for glyph in &line.glyphs {
+			let Some(data) = glyphs.get(&(glyph.font_index, glyph.id)) else {continue;};
+			let (_id, _pixels, width, height, x_offset, y_offset) = data;
+			if *width == 0 || *height == 0 {continue;}
+			let (x, y) = (line_x + glyph.x - x_offset, line.y + glyph.y - y_offset);
+			// sub-pixel glyphs are snapped the same way as in `render_text_subpixel()`
+			let (x, y) = if subpixel {(x.floor() as i32, y.floor() as i32)} else {(x.round() as i32, y.round() as i32)};
+			min_x = min_x.min(x);
+			min_y = min_y.min(y);
+			max_x = max_x.max(x + *width as i32);
+			max_y = max_y.max(y + *height as i32);
+			placed_glyphs.push((data, x, y));
+		}
+	}
+	
+	// blend glyphs into the image
+	let (width, height) = ((max_x - min_x) as usize, (max_y - min_y) as usize);
+	let background = [background.r, background.g, background.b, background.a];
+	let mut pixels = background.repeat(width * height);
+	for ((_id, glyph_pixels, glyph_width, glyph_height, _x_offset, _y_offset), x, y) in placed_glyphs {
+		let (x, y) = ((x - min_x) as usize, (y - min_y) as usize);
+		for glyph_y in 0..*glyph_height as usize {
+			for glyph_x in 0..*glyph_width as usize {
+				let src_i = (glyph_x + glyph_y * *glyph_width as usize) * 4;
+				let dst_i = (x + glyph_x + (y + glyph_y) * width) * 4;
+				let (src, dst) = (&glyph_pixels[src_i .. src_i + 4], &mut pixels[dst_i .. dst_i + 4]);
+				if subpixel {
+					for channel in 0..4 {
+						if src[channel].abs_diff(background[channel]) > dst[channel].abs_diff(background[channel]) {
+							dst[channel] = src[channel];
+						}
+					}
+				} else {
+					let (src_alpha, dst_alpha) = (src[3] as u16, dst[3] as u16);
+					dst[3] = (src_alpha + dst_alpha * (255 - src_alpha) / 255) as u8;
+				}
+			}
+		}
+	}
+	
+	RasterizedText {
+		pixels,
+		width: width as u32,
+		height: height as u32,
+		anchor_x: -min_x as f32,
+		baseline: -min_y as f32,
+		metrics,
+	}
+}
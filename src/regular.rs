@@ -1,109 +1,139 @@
 use crate::*;
-use std::{f32, sync::Mutex};
-use ab_glyph::{Glyph, PxScale, PxScaleFont, ScaleFont};
-use sdl3::{pixels::{Color, PixelFormat}, rect::Rect, sys::pixels::SDL_PixelFormat};
+use std::{f32, sync::Mutex, time::Instant};
+use ab_glyph::{point, Glyph, GlyphId, PxScale, PxScaleFont, ScaleFont};
+use sdl3::{pixels::Color, render::{FRect, RenderTarget, TextureCreator}};
 
 
 
 /// Renders text without sub-pixel rendering (a bit faster and easier to use, but looks a bit pixelated)
-pub fn render_text_regular<'a, 'b, F: ThreadSafeFont>(text: impl AsRef<str>, x: i32, y: i32, settings: &mut TextRenderingSettings<'a, 'b, F>) -> Result<(), RenderTextError> {
-	let (text, size, h_align, v_align, foreground, texture_creator) = (text.as_ref(), settings.size, settings.h_align, settings.v_align, settings.foreground, settings.texture_creator);
+pub fn render_text_regular<'a, 'b, F: ThreadSafeFont, T: RenderTarget>(text: impl AsRef<str>, x: f32, y: f32, settings: &mut TextRenderingSettings<'a, 'b, F, T>) -> Result<(), RenderTextError> {
+	let text = text.as_ref();
 	if text.is_empty() {return Ok(());}
-	let font = settings.text_cache.font.as_scaled(PxScale::from(100.0));
+	let layout = TextLayout::new(text, settings);
+	render_layout_regular(&layout, x, y, settings)
+}
+
+/// Renders text that has already been laid out, without sub-pixel rendering
+/// 
+/// NOTE: the layout's size, line spacing, and wrapping are used instead of the ones in `settings`
+pub fn render_layout_regular<'a, 'b, F: ThreadSafeFont, T: RenderTarget>(layout: &TextLayout, x: f32, y: f32, settings: &mut TextRenderingSettings<'a, 'b, F, T>) -> Result<(), RenderTextError> {
+	let (size, h_align, v_align, foreground, texture_creator) = (layout.size, settings.h_align, settings.v_align, settings.foreground, settings.texture_creator);
+	let (font_height, layout) = (layout.font_height(), &layout.text);
+	let size_bucket = get_size_bucket(size);
+	let curve = get_coverage_curve(settings.gamma_correction, foreground);
+	let glyphs = layout.lines.iter().flat_map(|line| {
+		let x = x + h_align.get_offset(line.width, line.rtl);
+		line.glyphs.iter().map(move |glyph| (glyph.font_index, glyph.id, split_subpixel_position(x + glyph.x).1))
+	});
+	cache_glyphs_regular(glyphs, size_bucket, curve, settings.text_cache, texture_creator)?;
+	let scale = size / size_bucket as f32;
+	
+	// align & render chars
+	let y = y + v_align.get_offset(font_height, layout.lines_height());
+	let mut batch = GlyphBatch::new();
+	for line in &layout.lines {
+		let (x, y) = (x + h_align.get_offset(line.width, line.rtl), y + line.y);
+		for glyph in &line.glyphs {
+			let (pixel_x, phase) = split_subpixel_position(x + glyph.x);
+			let texture_data = settings.text_cache.map_regular.get(&(glyph.font_index, glyph.id, size_bucket, phase, curve));
+			if let Some(cached_glyph) = texture_data {
+				// the glyph was rasterized with its origin at `phase_offset`, so its left edge lines up with the pixel grid, and the baseline is snapped to a whole pixel (the same as in `rasterize_text_regular()`) so that glyphs drawn at their bucket size aren't resampled vertically
+				let phase_offset = get_phase_offset(phase);
+				let dst = FRect::new(pixel_x + phase_offset - (cached_glyph.x_offset + phase_offset) * scale, (y + glyph.y).round() - cached_glyph.y_offset * scale, cached_glyph.src.width() as f32 * scale, cached_glyph.src.height() as f32 * scale);
+				batch.push_glyph(cached_glyph, &settings.text_cache.atlas, dst, foreground);
+			}
+		}
+	}
+	
+	batch.draw(settings.canvas, &settings.text_cache.atlas)?;
+	Ok(())
+}
+
+
+
+/// Rounds a font size to the size that regular glyphs are rasterized at, with larger steps for larger sizes (so the glyphs are scaled by at most ~3% when drawn, while limiting how many sizes are cached)
+pub(crate) fn get_size_bucket(size: f32) -> u32 {
+	let step = match size {
+		..=32.0 => 1.0,
+		..=64.0 => 2.0,
+		..=128.0 => 4.0,
+		_ => 8.0,
+	};
+	((size / step).round() * step).max(1.0) as u32
+}
+
+/// Gets which coverage curve regular glyphs should be rasterized with, where the foreground's luminance is rounded so that only a few versions of each glyph are cached
+pub(crate) fn get_coverage_curve(gamma_correction: Option<GammaCorrection>, foreground: Color) -> CoverageCurve {
+	gamma_correction.map(|gamma_correction| (gamma_correction, (gamma_correction.get_luminance(foreground) * 8.0).round() as u8))
+}
+
+/// Rasterizes (in parallel) and uploads any of the glyphs that aren't cached yet, and marks the rest as used
+pub(crate) fn cache_glyphs_regular<'a, F: ThreadSafeFont, C>(glyphs: impl Iterator<Item = (usize, GlyphId, u32)> + Send, size_bucket: u32, curve: CoverageCurve, text_cache: &mut TextCache<'a, F, C>, texture_creator: &'a TextureCreator<C>) -> Result<(), RenderTextError> {
+	let fonts = text_cache.fonts.iter().map(|font| font.as_scaled(PxScale::from(size_bucket as f32))).collect::<Vec<_>>();
 	
-	// convert chars to glyphs & rasterize uncached glyphs
-	let mut glyphs = Vec::with_capacity(text.len());
+	// rasterize uncached glyphs
+	let rasterize_start = Instant::now();
+	let (mut hits, mut misses) = (0, 0);
 	let new_textures = Mutex::new(vec!());
-	let set_regular = &mut settings.text_cache.set_regular;
+	text_cache.use_counter += 1;
+	let (use_counter, pin_glyphs) = (text_cache.use_counter, text_cache.pin_glyphs);
+	let set_regular = &mut text_cache.set_regular;
+	let map_regular = &mut text_cache.map_regular;
 	rayon::scope(|s| {
-		for c in text.chars() {
-			let glyph = font.scaled_glyph(c);
-			let is_new = set_regular.insert((c, foreground));
+		for (font_index, id, phase) in glyphs {
+			let key = (font_index, id, size_bucket, phase, curve);
+			let is_new = set_regular.insert(key);
 			if is_new {
+				misses += 1;
 				let new_textures = &new_textures;
-				let glyph = glyph.clone();
+				let font = &fonts[font_index];
+				let glyph = id.with_scale_and_position(size_bucket as f32, point(get_phase_offset(phase), 0.0));
 				s.spawn(move |_s| {
-					let result = rasterize_glyph_regular(glyph.clone(), c, foreground, &font);
+					let result = rasterize_glyph_regular(glyph, curve, font).map(|data| (font_index, phase, data));
 					new_textures.lock().unwrap().push(result);
 				});
+			} else {
+				hits += 1;
+				if let Some(cached_glyph) = map_regular.get_mut(&key) {
+					cached_glyph.touch(use_counter, pin_glyphs);
+				}
 			}
-			glyphs.push((c, glyph));
 		}
 	});
+	let rasterize_time = rasterize_start.elapsed();
 	
 	// upload new glyph textures to gpu
+	let upload_start = Instant::now();
 	for texture_data in new_textures.into_inner().unwrap() {
-		let Some((c, pixels, width, height, x_offset, y_offset)) = texture_data else {continue;};
-		let mut texture = texture_creator.create_texture(
-			Some(unsafe {PixelFormat::from_ll(SDL_PixelFormat::ABGR8888)}),
-			sdl3::render::TextureAccess::Static,
-			width,
-			height,
-		)?;
-		texture.update(None, &pixels, width as usize * 4)?;
-		settings.text_cache.map_regular.insert((c, foreground), (texture, width, height, x_offset, y_offset));
+		let Some((font_index, phase, (id, pixels, width, height, x_offset, y_offset))) = texture_data else {continue;};
+		if width == 0 || height == 0 {continue;}
+		let cached_glyph = text_cache.upload_glyph(&pixels, width, height, x_offset, y_offset, texture_creator)?;
+		text_cache.map_regular.insert((font_index, id, size_bucket, phase, curve), cached_glyph);
 	}
-	let font = settings.text_cache.font.as_scaled(PxScale::from(size));
-	
-	// get text width & align properly
-	let mut width = 0.0;
-	let (c, first_glyph) = &glyphs[0];
-	width += font.h_advance(first_glyph.id);
-	width += size * EXTRA_CHAR_SPACING;
-	if c.is_whitespace() {width += size * EXTRA_WHITESPACE_SPACING;}
-	for [(_prev_c, prev_glyph), (c, glyph)] in glyphs.array_windows() {
-		width += font.kern(prev_glyph.id, glyph.id);
-		width += font.h_advance(glyph.id);
-		width += size * EXTRA_CHAR_SPACING;
-		if c.is_whitespace() {width += size * EXTRA_WHITESPACE_SPACING;}
-	}
-	width -= size * EXTRA_CHAR_SPACING;
-	let mut x = x as f32 + h_align.get_offset(width);
-	let y = y as f32 + v_align.get_offset(font.height());
-	
-	// render first char
-	if let Some((c, first_glyph)) = glyphs.first() {
-		let texture_data = settings.text_cache.map_regular.get(&(*c, foreground));
-		if let Some((texture, width, height, x_offset, y_offset)) = texture_data {
-			let dst = Rect::new((x - *x_offset * size / 100.0) as i32, (y - *y_offset * size / 100.0) as i32, (size * (*width as f32 / 100.0)) as u32, (size * (*height as f32 / 100.0)) as u32);
-			settings.canvas.copy(texture, None, dst)?;
-		}
-		x += font.h_advance(first_glyph.id);
-		x += size * EXTRA_CHAR_SPACING;
-		if c.is_whitespace() {x += size * EXTRA_WHITESPACE_SPACING;}
-	}
-	
-	// render remaining chars (with kerning)
-	for [(_prev_c, prev_glyph), (c, glyph)] in glyphs.array_windows() {
-		x += font.kern(prev_glyph.id, glyph.id);
-		let texture_data = settings.text_cache.map_regular.get(&(*c, foreground));
-		if let Some((texture, width, height, x_offset, y_offset)) = texture_data {
-			let dst = Rect::new((x - *x_offset * size / 100.0) as i32, (y - *y_offset * size / 100.0) as i32, (size * (*width as f32 / 100.0)) as u32, (size * (*height as f32 / 100.0)) as u32);
-			settings.canvas.copy(texture, None, dst)?;
-		}
-		x += font.h_advance(glyph.id);
-		x += size * EXTRA_CHAR_SPACING;
-		if c.is_whitespace() {x += size * EXTRA_WHITESPACE_SPACING;}
-	}
-	
+	text_cache.record_stats(hits, misses, rasterize_time, upload_start.elapsed());
+	text_cache.evict_if_needed();
 	Ok(())
 }
 
 
 
-fn rasterize_glyph_regular(glyph: Glyph, c: char, foreground: Color, font: &PxScaleFont<&impl ThreadSafeFont>) -> Option<(char, Vec<u8>, u32, u32, f32, f32)> {
+/// Rasterizes the glyph as white with the coverage in the alpha channel, so that it can be drawn in any color with color modulation
+pub(crate) fn rasterize_glyph_regular(glyph: Glyph, curve: CoverageCurve, font: &PxScaleFont<&impl ThreadSafeFont>) -> Option<(GlyphId, Vec<u8>, u32, u32, f32, f32)> {
 	
+	let id = glyph.id;
 	let glyph = font.outline_glyph(glyph)?;
 	let bounds = glyph.px_bounds();
 	
-	let alpha = foreground.a as f32;
-	let foreground = [foreground.r, foreground.g, foreground.b, 0];
 	let width = bounds.width().ceil() as u32;
 	let height = bounds.height().ceil() as u32;
-	let mut pixels = foreground.repeat((width * height) as usize);
+	let mut pixels = [255, 255, 255, 0].repeat((width * height) as usize);
 	glyph.draw(|x, y, v| {
-		pixels[((x + y * width) * 4 + 3) as usize] = (alpha * v.powf(REGULAR_VALUE_POW)) as u8;
+		let value = match curve {
+			Some((gamma_correction, luminance)) => gamma_correction.correct_coverage(v.clamp(0.0, 1.0), luminance as f32 / 8.0),
+			None => v.powf(REGULAR_VALUE_POW),
+		};
+		pixels[((x + y * width) * 4 + 3) as usize] = (255.0 * value) as u8;
 	});
 	
-	Some((c, pixels, width, height, -bounds.min.x, -bounds.min.y))
+	Some((id, pixels, width, height, -bounds.min.x, -bounds.min.y))
 }
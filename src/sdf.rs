@@ -0,0 +1,240 @@
+use crate::*;
+use std::{collections::HashSet, sync::Mutex, time::Instant};
+use ab_glyph::{Glyph, GlyphId, PxScale, PxScaleFont, ScaleFont};
+use sdl3::render::{FRect, RenderTarget, TextureCreator};
+
+
+
+/// The size that each glyph's distance field is generated at
+pub(crate) const SDF_SIZE: f32 = 64.0;
+/// How far past the outline (in pixels at `SDF_SIZE`) distance fields extend, which limits how soft the edges can get
+pub(crate) const SDF_SPREAD: usize = 6;
+
+
+
+/// A glyph's signed distance field, which is only generated once per glyph (no matter how many sizes it's drawn at) and kept on the cpu
+pub(crate) struct SdfGlyph {
+	// the distance from each pixel's center to the outline, in pixels at `SDF_SIZE` (positive is inside)
+	distances: Vec<f32>,
+	width: usize,
+	height: usize,
+	// the position of the field's top-left corner relative to the glyph's origin
+	left: f32,
+	top: f32,
+}
+
+
+
+/// Renders text from signed distance fields, which is meant for text whose size changes often (like on a zoomable canvas)
+/// 
+/// Each glyph's outline is only rasterized once into a distance field, and `settings.edge_softness` controls how blurry the edges are
+/// 
+/// NOTE: sdl3's renderer doesn't support custom shaders, so the distance fields are still turned into coverage on the cpu and cached in the atlas, but only at power-of-two sizes (see `get_sdf_bucket()`), and every size in between is drawn by scaling the next larger one down on the gpu. This means each glyph is cached once per power-of-two size and edge softness, instead of once per size like `render_text_regular()`
+pub fn render_text_sdf<'a, 'b, F: ThreadSafeFont, T: RenderTarget>(text: impl AsRef<str>, x: f32, y: f32, settings: &mut TextRenderingSettings<'a, 'b, F, T>) -> Result<(), RenderTextError> {
+	let text = text.as_ref();
+	if text.is_empty() {return Ok(());}
+	let layout = TextLayout::new(text, settings);
+	render_layout_sdf(&layout, x, y, settings)
+}
+
+/// Renders text that has already been laid out, from signed distance fields
+/// 
+/// NOTE: the layout's size, line spacing, and wrapping are used instead of the ones in `settings`
+pub fn render_layout_sdf<'a, 'b, F: ThreadSafeFont, T: RenderTarget>(layout: &TextLayout, x: f32, y: f32, settings: &mut TextRenderingSettings<'a, 'b, F, T>) -> Result<(), RenderTextError> {
+	let (size, h_align, v_align, foreground, texture_creator) = (layout.size, settings.h_align, settings.v_align, settings.foreground, settings.texture_creator);
+	let (font_height, layout) = (layout.font_height(), &layout.text);
+	let size_bucket = get_sdf_bucket(size);
+	let softness_key = get_softness_key(settings.edge_softness);
+	let glyphs = layout.lines.iter().flat_map(|line| line.glyphs.iter().map(|glyph| (glyph.font_index, glyph.id)));
+	cache_glyphs_sdf(glyphs, size_bucket, softness_key, settings.text_cache, texture_creator)?;
+	let scale = size / size_bucket as f32;
+	
+	// align & render chars
+	let y = y + v_align.get_offset(font_height, layout.lines_height());
+	let mut batch = GlyphBatch::new();
+	for line in &layout.lines {
+		let (x, y) = (x + h_align.get_offset(line.width, line.rtl), y + line.y);
+		for glyph in &line.glyphs {
+			let texture_data = settings.text_cache.map_sdf.get(&(glyph.font_index, glyph.id, size_bucket, softness_key));
+			if let Some(cached_glyph) = texture_data {
+				// glyphs are almost always scaled, so there are no sub-pixel phases and the gpu's filtering handles the horizontal position, but the baseline is still snapped to a whole pixel like in `render_layout_regular()`
+				let dst = FRect::new(x + glyph.x - cached_glyph.x_offset * scale, (y + glyph.y).round() - cached_glyph.y_offset * scale, cached_glyph.src.width() as f32 * scale, cached_glyph.src.height() as f32 * scale);
+				batch.push_glyph(cached_glyph, &settings.text_cache.atlas, dst, foreground);
+			}
+		}
+	}
+	
+	batch.draw(settings.canvas, &settings.text_cache.atlas)?;
+	Ok(())
+}
+
+
+
+/// Gets the power-of-two size that glyphs are derived at for this size, which is always at least as large as `size` so that glyphs are only ever scaled down
+pub(crate) fn get_sdf_bucket(size: f32) -> u32 {
+	(size.ceil().max(8.0) as u32).next_power_of_two()
+}
+
+/// Rounds the edge softness to sixteenths of a pixel, so that it can be used as part of a cache key
+pub(crate) fn get_softness_key(edge_softness: f32) -> u32 {
+	(edge_softness * 16.0).round().max(1.0) as u32
+}
+
+/// Generates the distance fields of any glyphs that don't have one yet, then derives (in parallel) and uploads any of the glyphs that aren't cached at this size bucket yet, and marks the rest as used
+pub(crate) fn cache_glyphs_sdf<'a, F: ThreadSafeFont, C>(glyphs: impl Iterator<Item = (usize, GlyphId)>, size_bucket: u32, softness_key: u32, text_cache: &mut TextCache<'a, F, C>, texture_creator: &'a TextureCreator<C>) -> Result<(), RenderTextError> {
+	
+	// find uncached glyphs
+	let (mut hits, mut new_keys) = (0, vec!());
+	text_cache.use_counter += 1;
+	let (use_counter, pin_glyphs) = (text_cache.use_counter, text_cache.pin_glyphs);
+	for (font_index, id) in glyphs {
+		let key = (font_index, id, size_bucket, softness_key);
+		if text_cache.set_sdf.insert(key) {
+			new_keys.push(key);
+		} else {
+			hits += 1;
+			if let Some(cached_glyph) = text_cache.map_sdf.get_mut(&key) {
+				cached_glyph.touch(use_counter, pin_glyphs);
+			}
+		}
+	}
+	
+	// generate missing distance fields
+	let rasterize_start = Instant::now();
+	let fonts = text_cache.fonts.iter().map(|font| font.as_scaled(PxScale::from(SDF_SIZE))).collect::<Vec<_>>();
+	let missing_fields = new_keys.iter().map(|key| (key.0, key.1)).filter(|key| !text_cache.sdf_fields.contains_key(key)).collect::<HashSet<_>>();
+	let new_fields = Mutex::new(vec!());
+	rayon::scope(|s| {
+		for (font_index, id) in missing_fields {
+			let new_fields = &new_fields;
+			let font = &fonts[font_index];
+			s.spawn(move |_s| {
+				let field = generate_sdf(id.with_scale(SDF_SIZE), font);
+				new_fields.lock().unwrap().push(((font_index, id), field));
+			});
+		}
+	});
+	text_cache.sdf_fields.extend(new_fields.into_inner().unwrap());
+	
+	// turn the distance fields into coverage at this size bucket
+	let new_textures = Mutex::new(vec!());
+	let sdf_fields = &text_cache.sdf_fields;
+	let softness = softness_key as f32 / 16.0;
+	rayon::scope(|s| {
+		for key in &new_keys {
+			let Some(Some(field)) = sdf_fields.get(&(key.0, key.1)) else {continue;};
+			let new_textures = &new_textures;
+			s.spawn(move |_s| {
+				let data = derive_glyph_sdf(field, size_bucket, softness);
+				new_textures.lock().unwrap().push((*key, data));
+			});
+		}
+	});
+	let rasterize_time = rasterize_start.elapsed();
+	
+	// upload new glyph textures to gpu
+	let upload_start = Instant::now();
+	for (key, (pixels, width, height, x_offset, y_offset)) in new_textures.into_inner().unwrap() {
+		if width == 0 || height == 0 {continue;}
+		let cached_glyph = text_cache.upload_glyph(&pixels, width, height, x_offset, y_offset, texture_creator)?;
+		text_cache.map_sdf.insert(key, cached_glyph);
+	}
+	text_cache.record_stats(hits, new_keys.len() as u64, rasterize_time, upload_start.elapsed());
+	text_cache.evict_if_needed();
+	Ok(())
+}
+
+
+
+/// Rasterizes the glyph's outline and finds each pixel's signed distance to the outline (returns `None` for glyphs without an outline)
+pub(crate) fn generate_sdf(glyph: Glyph, font: &PxScaleFont<&impl ThreadSafeFont>) -> Option<SdfGlyph> {
+	
+	let glyph = font.outline_glyph(glyph)?;
+	let bounds = glyph.px_bounds();
+	let width = bounds.width().ceil() as usize + SDF_SPREAD * 2;
+	let height = bounds.height().ceil() as usize + SDF_SPREAD * 2;
+	let mut coverage = vec![0.0; width * height];
+	glyph.draw(|x, y, v| {
+		coverage[x as usize + SDF_SPREAD + (y as usize + SDF_SPREAD) * width] = v;
+	});
+	
+	// partially covered pixels are on the outline, so their distance is estimated from their coverage, and every other pixel's distance is the distance to the closest pixel that's on (or across) the outline plus that pixel's distance
+	let spread = SDF_SPREAD as isize;
+	let is_partial = |value: f32| value > 0.01 && value < 0.99;
+	let mut distances = vec![0.0; width * height];
+	for y in 0..height {
+		for x in 0..width {
+			let value = coverage[x + y * width];
+			if is_partial(value) {
+				distances[x + y * width] = value - 0.5;
+				continue;
+			}
+			let is_inside = value >= 0.5;
+			let mut closest = SDF_SPREAD as f32;
+			for offset_y in -spread ..= spread {
+				for offset_x in -spread ..= spread {
+					let (other_x, other_y) = (x as isize + offset_x, y as isize + offset_y);
+					if other_x < 0 || other_y < 0 || other_x >= width as isize || other_y >= height as isize {continue;}
+					let other_value = coverage[other_x as usize + other_y as usize * width];
+					if !is_partial(other_value) && (other_value >= 0.5) == is_inside {continue;}
+					let other_distance = if is_inside {other_value - 0.5} else {0.5 - other_value};
+					let distance = ((offset_x * offset_x + offset_y * offset_y) as f32).sqrt() + other_distance;
+					closest = closest.min(distance);
+				}
+			}
+			distances[x + y * width] = if is_inside {closest} else {-closest};
+		}
+	}
+	
+	Some(SdfGlyph {
+		distances,
+		width,
+		height,
+		left: bounds.min.x - SDF_SPREAD as f32,
+		top: bounds.min.y - SDF_SPREAD as f32,
+	})
+}
+
+/// Samples the distance field at one size bucket, and turns it into white pixels with the coverage in the alpha channel (the same format as `rasterize_glyph_regular()`)
+/// 
+/// The edge softness is in pixels at the bucket size, so edges drawn below the bucket size are slightly sharper
+pub(crate) fn derive_glyph_sdf(field: &SdfGlyph, size_bucket: u32, edge_softness: f32) -> (Vec<u8>, u32, u32, f32, f32) {
+	let scale = size_bucket as f32 / SDF_SIZE;
+	
+	// only the outline's bounds plus however far the soft edge reaches are kept, instead of the whole spread
+	let spread = SDF_SPREAD as f32;
+	let margin = edge_softness * 0.5 + 1.0;
+	let min_x = ((field.left + spread) * scale - margin).floor();
+	let min_y = ((field.top + spread) * scale - margin).floor();
+	let max_x = ((field.left + field.width as f32 - spread) * scale + margin).ceil();
+	let max_y = ((field.top + field.height as f32 - spread) * scale + margin).ceil();
+	let (width, height) = ((max_x - min_x) as u32, (max_y - min_y) as u32);
+	
+	let mut pixels = [255, 255, 255, 0].repeat((width * height) as usize);
+	for y in 0..height {
+		for x in 0..width {
+			// position of this pixel's center within the field, where each field pixel's center is at a whole number
+			let field_x = (min_x + x as f32 + 0.5) / scale - field.left - 0.5;
+			let field_y = (min_y + y as f32 + 0.5) / scale - field.top - 0.5;
+			let distance = sample_sdf(field, field_x, field_y) * scale;
+			let value = (0.5 + distance / edge_softness).clamp(0.0, 1.0);
+			pixels[((x + y * width) * 4 + 3) as usize] = (255.0 * value.powf(REGULAR_VALUE_POW)) as u8;
+		}
+	}
+	
+	(pixels, width, height, -min_x, -min_y)
+}
+
+/// Bilinearly samples the distance field (anything outside the field counts as fully outside)
+fn sample_sdf(field: &SdfGlyph, x: f32, y: f32) -> f32 {
+	let get = |x: isize, y: isize| {
+		if x < 0 || y < 0 || x >= field.width as isize || y >= field.height as isize {return -(SDF_SPREAD as f32);}
+		field.distances[x as usize + y as usize * field.width]
+	};
+	let (x0, y0) = (x.floor(), y.floor());
+	let (tx, ty) = (x - x0, y - y0);
+	let (x0, y0) = (x0 as isize, y0 as isize);
+	let top = get(x0, y0) * (1.0 - tx) + get(x0 + 1, y0) * tx;
+	let bottom = get(x0, y0 + 1) * (1.0 - tx) + get(x0 + 1, y0 + 1) * tx;
+	top * (1.0 - ty) + bottom * ty
+}
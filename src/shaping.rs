@@ -0,0 +1,76 @@
+use crate::*;
+use ab_glyph::{GlyphId, PxScale, ScaleFont};
+use rustybuzz::{Direction, Face, UnicodeBuffer};
+use unicode_segmentation::UnicodeSegmentation;
+
+
+
+/// Lays out a line using rustybuzz, which handles ligatures, contextual alternates, mark positioning, and complex scripts
+/// 
+/// If `rtl` is `None`, the direction is guessed from the text
+/// 
+/// When fallback fonts are used, the text is split into runs of grapheme clusters that use the same font, and each run is shaped separately
+/// 
+/// Returns `None` if a font's data can't be read by rustybuzz (in which case the un-shaped layout is used instead)
+/// 
+/// NOTE: this always uses the first face in the font data, so fonts loaded from a later index of a font collection won't be shaped correctly
+pub(crate) fn shape_line<F: ThreadSafeFont>(text: &str, rtl: Option<bool>, size: f32, fonts: &[F]) -> Option<LaidOutLine> {
+	let rtl = match rtl {
+		Some(rtl) => rtl,
+		None => {
+			let mut buffer = UnicodeBuffer::new();
+			buffer.push_str(text);
+			buffer.guess_segment_properties();
+			buffer.direction() == Direction::RightToLeft
+		}
+	};
+	
+	// split into font runs: (font index, start byte, end byte), where each cluster uses the first font that has its base char (the same as the un-shaped layout)
+	let mut runs: Vec<(usize, usize, usize)> = vec!();
+	for (cluster_index, cluster) in text.grapheme_indices(true) {
+		let Some(c) = cluster.chars().next() else {continue;};
+		let font_index = find_font_for_char(c, 0, fonts);
+		match runs.last_mut() {
+			Some(run) if run.0 == font_index => run.2 = cluster_index + cluster.len(),
+			_ => runs.push((font_index, cluster_index, cluster_index + cluster.len())),
+		}
+	}
+	// runs are shaped in logical order, but right-to-left glyphs are output in visual order
+	if rtl {runs.reverse();}
+	
+	let mut glyphs = Vec::with_capacity(text.len());
+	let mut x = 0.0;
+	let mut prev_extra_spacing = 0.0;
+	for (font_index, start, end) in runs {
+		let font = &fonts[font_index];
+		let face = Face::from_slice(font.font_data(), 0)?;
+		let scaled_font = font.as_scaled(PxScale::from(size));
+		let (h_scale, v_scale) = (scaled_font.h_scale_factor(), scaled_font.v_scale_factor());
+		
+		let mut buffer = UnicodeBuffer::new();
+		buffer.push_str(&text[start .. end]);
+		buffer.guess_segment_properties();
+		buffer.set_direction(if rtl {Direction::RightToLeft} else {Direction::LeftToRight});
+		let output = rustybuzz::shape(&face, &[], buffer);
+		
+		for (info, position) in output.glyph_infos().iter().zip(output.glyph_positions()) {
+			let byte_index = start + info.cluster as usize;
+			let c = text[byte_index ..].chars().next().unwrap_or(' ');
+			let mut glyph_x = x + position.x_offset as f32 * h_scale;
+			let mut advance = position.x_advance as f32 * h_scale;
+			if advance == 0.0 {
+				// marks are positioned relative to the end of the previous glyph, so they shouldn't be moved by its extra spacing
+				glyph_x -= prev_extra_spacing;
+			} else {
+				let mut extra_spacing = size * EXTRA_CHAR_SPACING;
+				if c.is_whitespace() {extra_spacing += size * EXTRA_WHITESPACE_SPACING;}
+				advance += extra_spacing;
+				prev_extra_spacing = extra_spacing;
+			}
+			glyphs.push(LaidOutGlyph {c, font_index, id: GlyphId(info.glyph_id as u16), byte_index, x: glyph_x, y: -position.y_offset as f32 * v_scale, advance});
+			x += advance;
+		}
+	}
+	let width = if glyphs.is_empty() {0.0} else {x - size * EXTRA_CHAR_SPACING};
+	Some(LaidOutLine {glyphs, width, y: 0.0, rtl})
+}
@@ -1,158 +1,256 @@
 use crate::*;
-use std::{f32, sync::Mutex};
-use ab_glyph::{Glyph, PxScale, PxScaleFont, ScaleFont};
-use sdl3::{pixels::{Color, PixelFormat}, rect::Rect, sys::pixels::SDL_PixelFormat};
+use std::{f32, sync::Mutex, time::Instant};
+use ab_glyph::{point, Glyph, GlyphId, Point, PxScale, PxScaleFont, ScaleFont};
+use sdl3::{pixels::Color, render::{FRect, RenderTarget, TextureCreator}, sys::blendmode::{SDL_ComposeCustomBlendMode, SDL_BLENDFACTOR_ONE, SDL_BLENDFACTOR_ONE_MINUS_SRC_COLOR, SDL_BLENDFACTOR_ZERO, SDL_BLENDOPERATION_ADD}};
 
 
 
 /// Renders text with sub-pixel rendering (limited and a bit slower but looks really nice)
-pub fn render_text_subpixel<'a, 'b, F: ThreadSafeFont>(text: impl AsRef<str>, x: i32, y: i32, settings: &mut TextRenderingSettings<'a, 'b, F>) -> Result<(), RenderTextError> {
-	let (text, size, h_align, v_align, foreground, background, texture_creator) = (text.as_ref(), settings.size as u32, settings.h_align, settings.v_align, settings.foreground, settings.background, settings.texture_creator);
+pub fn render_text_subpixel<'a, 'b, F: ThreadSafeFont, T: RenderTarget>(text: impl AsRef<str>, x: f32, y: f32, settings: &mut TextRenderingSettings<'a, 'b, F, T>) -> Result<(), RenderTextError> {
+	let text = text.as_ref();
 	if text.is_empty() {return Ok(());}
-	let mut font = settings.text_cache.font.as_scaled(PxScale::from(size as f32));
+	let layout = TextLayout::new_with(text, settings.size as u32 as f32, settings.line_spacing, settings.max_width, settings.text_cache);
+	render_layout_subpixel(&layout, x, y, settings)
+}
+
+/// Renders text that has already been laid out, with sub-pixel rendering
+/// 
+/// NOTE: the layout's size, line spacing, and wrapping are used instead of the ones in `settings` (and the size is converted to u32, so the layout should be created with a whole number size)
+pub fn render_layout_subpixel<'a, 'b, F: ThreadSafeFont, T: RenderTarget>(layout: &TextLayout, x: f32, y: f32, settings: &mut TextRenderingSettings<'a, 'b, F, T>) -> Result<(), RenderTextError> {
+	let batch = batch_layout_subpixel(layout, x, y, settings.foreground, settings.background, settings.gamma_correction, settings)?;
+	batch.draw(settings.canvas, &settings.text_cache.atlas)?;
+	Ok(())
+}
+
+
+
+/// Renders text with sub-pixel rendering, blended onto whatever is already on the canvas instead of pre-blended onto `settings.background` (so it can be drawn over gradients and images)
+/// 
+/// Glyphs are cached as per-channel coverage (which doesn't depend on the colors) and each string is drawn in two passes with custom blend modes: the first darkens each channel of the canvas by the glyph's coverage for that channel, and the second adds the foreground color scaled by the same coverage
+/// 
+/// NOTE: sdl3's software renderer doesn't support custom blend modes, so this returns an error when drawing to a `Canvas<Surface>` (`render_text_subpixel()` still works there)
+pub fn render_text_subpixel_blended<'a, 'b, F: ThreadSafeFont, T: RenderTarget>(text: impl AsRef<str>, x: f32, y: f32, settings: &mut TextRenderingSettings<'a, 'b, F, T>) -> Result<(), RenderTextError> {
+	let text = text.as_ref();
+	if text.is_empty() {return Ok(());}
+	let layout = TextLayout::new_with(text, settings.size as u32 as f32, settings.line_spacing, settings.max_width, settings.text_cache);
+	render_layout_subpixel_blended(&layout, x, y, settings)
+}
+
+/// Renders text that has already been laid out, with sub-pixel rendering that is blended onto the canvas (see `render_text_subpixel_blended()`)
+/// 
+/// NOTE: the layout's size, line spacing, and wrapping are used instead of the ones in `settings` (and the size is converted to u32, so the layout should be created with a whole number size)
+pub fn render_layout_subpixel_blended<'a, 'b, F: ThreadSafeFont, T: RenderTarget>(layout: &TextLayout, x: f32, y: f32, settings: &mut TextRenderingSettings<'a, 'b, F, T>) -> Result<(), RenderTextError> {
+	// white on black gives glyphs whose color channels are just the coverage of each sub-pixel (these are blended by the gpu, so they can't be gamma corrected)
+	let mut batch = batch_layout_subpixel(layout, x, y, Color::WHITE, Color::BLACK, None, settings)?;
+	let foreground = settings.foreground;
+	let alpha = foreground.a as u16;
+	
+	// dst = dst * (1 - coverage)
+	let darken_mode = unsafe {SDL_ComposeCustomBlendMode(SDL_BLENDFACTOR_ZERO, SDL_BLENDFACTOR_ONE_MINUS_SRC_COLOR, SDL_BLENDOPERATION_ADD, SDL_BLENDFACTOR_ZERO, SDL_BLENDFACTOR_ONE, SDL_BLENDOPERATION_ADD)};
+	batch.set_color(Color::RGB(alpha as u8, alpha as u8, alpha as u8));
+	batch.draw_with_blend_mode(settings.canvas, &settings.text_cache.atlas, darken_mode)?;
+	
+	// dst = dst + foreground * coverage
+	let add_mode = unsafe {SDL_ComposeCustomBlendMode(SDL_BLENDFACTOR_ONE, SDL_BLENDFACTOR_ONE, SDL_BLENDOPERATION_ADD, SDL_BLENDFACTOR_ZERO, SDL_BLENDFACTOR_ONE, SDL_BLENDOPERATION_ADD)};
+	let premultiply = |channel: u8| (channel as u16 * alpha / 255) as u8;
+	batch.set_color(Color::RGB(premultiply(foreground.r), premultiply(foreground.g), premultiply(foreground.b)));
+	batch.draw_with_blend_mode(settings.canvas, &settings.text_cache.atlas, add_mode)?;
+	
+	Ok(())
+}
+
+
+
+/// Caches the layout's glyphs with the given colors (and gamma correction) and collects their quads (which are drawn without any color modulation)
+fn batch_layout_subpixel<'a, 'b, F: ThreadSafeFont, T: RenderTarget>(layout: &TextLayout, x: f32, y: f32, foreground: Color, background: Color, gamma_correction: Option<GammaCorrection>, settings: &mut TextRenderingSettings<'a, 'b, F, T>) -> Result<GlyphBatch, RenderTextError> {
+	let (size, h_align, v_align, texture_creator) = (layout.size as u32, settings.h_align, settings.v_align, settings.texture_creator);
+	let (order, filter) = (settings.subpixel_order, settings.subpixel_filter);
+	let (font_height, layout) = (layout.font_height(), &layout.text);
+	let glyphs = layout.lines.iter().flat_map(|line| {
+		let x = x + h_align.get_offset(line.width, line.rtl);
+		line.glyphs.iter().map(move |glyph| (glyph.font_index, glyph.id, split_subpixel_position(x + glyph.x).1))
+	});
+	cache_glyphs_subpixel(glyphs, size, foreground, background, order, filter, gamma_correction, settings.text_cache, texture_creator)?;
+	
+	// align & render chars
+	let y = y + v_align.get_offset(font_height, layout.lines_height());
+	let mut batch = GlyphBatch::new();
+	for line in &layout.lines {
+		let (x, y) = (x + h_align.get_offset(line.width, line.rtl), y + line.y);
+		for glyph in &line.glyphs {
+			let (pixel_x, phase) = split_subpixel_position(x + glyph.x);
+			let texture_data = settings.text_cache.map_subpixel.get(&(glyph.font_index, glyph.id, size, foreground, background, phase, order, filter, gamma_correction));
+			if let Some(cached_glyph) = texture_data {
+				// sub-pixel textures are drawn 1:1, so they're snapped to whole pixels to keep them from being blurred (the glyph was rasterized with its origin at the phase offset and its offsets are whole numbers, so the horizontal position is still exact)
+				let dst = FRect::new(pixel_x - cached_glyph.x_offset, (y + glyph.y - cached_glyph.y_offset).floor(), cached_glyph.src.width() as f32, cached_glyph.src.height() as f32);
+				batch.push_glyph(cached_glyph, &settings.text_cache.atlas, dst, Color::WHITE);
+			}
+		}
+	}
+	
+	Ok(batch)
+}
+
+
+
+/// Rasterizes (in parallel) and uploads any of the glyphs that aren't cached yet, and marks the rest as used
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn cache_glyphs_subpixel<'a, F: ThreadSafeFont, C>(glyphs: impl Iterator<Item = (usize, GlyphId, u32)> + Send, size: u32, foreground: Color, background: Color, order: SubpixelOrder, filter: SubpixelFilter, gamma_correction: Option<GammaCorrection>, text_cache: &mut TextCache<'a, F, C>, texture_creator: &'a TextureCreator<C>) -> Result<(), RenderTextError> {
+	let fonts = get_subpixel_fonts(&text_cache.fonts, size, order);
 	
-	// convert chars to glyphs & rasterize uncached glyphs
-	font.scale.x *= 3.0; // for sub-pixel rendering
-	let mut glyphs = Vec::with_capacity(text.len());
+	// rasterize uncached glyphs
+	let rasterize_start = Instant::now();
+	let (mut hits, mut misses) = (0, 0);
 	let new_textures = Mutex::new(vec!());
-	let set_subpixel = &mut settings.text_cache.set_subpixel;
+	text_cache.use_counter += 1;
+	let (use_counter, pin_glyphs) = (text_cache.use_counter, text_cache.pin_glyphs);
+	let set_subpixel = &mut text_cache.set_subpixel;
+	let map_subpixel = &mut text_cache.map_subpixel;
 	rayon::scope(|s| {
-		for c in text.chars() {
-			let glyph = font.scaled_glyph(c);
-			let is_new = set_subpixel.insert((c, size, foreground, background));
+		for (font_index, id, phase) in glyphs {
+			let key = (font_index, id, size, foreground, background, phase, order, filter, gamma_correction);
+			let is_new = set_subpixel.insert(key);
 			if is_new {
+				misses += 1;
 				let new_textures = &new_textures;
-				let glyph = glyph.clone();
+				let font = &fonts[font_index];
+				let glyph = id.with_scale_and_position(font.scale, get_subpixel_glyph_position(phase, order));
 				s.spawn(move |_s| {
-					let result = rasterize_glyph_subpixel(glyph.clone(), c, foreground, background, &font);
+					let result = rasterize_glyph_subpixel(glyph, foreground, background, order, filter, gamma_correction, font).map(|data| (font_index, phase, data));
 					new_textures.lock().unwrap().push(result);
 				});
+			} else {
+				hits += 1;
+				if let Some(cached_glyph) = map_subpixel.get_mut(&key) {
+					cached_glyph.touch(use_counter, pin_glyphs);
+				}
 			}
-			glyphs.push((c, glyph));
 		}
 	});
+	let rasterize_time = rasterize_start.elapsed();
 	
 	// upload new glyph textures to gpu
+	let upload_start = Instant::now();
 	for texture_data in new_textures.into_inner().unwrap() {
-		let Some((c, pixels, width, height, x_offset, y_offset)) = texture_data else {continue;};
-		let mut texture = texture_creator.create_texture(
-			Some(unsafe {PixelFormat::from_ll(SDL_PixelFormat::ABGR8888)}),
-			sdl3::render::TextureAccess::Static,
-			width,
-			height,
-		)?;
-		texture.update(None, &pixels, width as usize * 4)?;
-		settings.text_cache.map_subpixel.insert((c, size, foreground, background), (texture, width, height, x_offset, y_offset));
-	}
-	
-	// get text width & align properly
-	font.scale.x /= 3.0; // undo sub-pixel rendering scaling to do spacing
-	let mut width = 0.0;
-	let (c, first_glyph) = &glyphs[0];
-	width += font.h_advance(first_glyph.id);
-	width += size as f32 * EXTRA_CHAR_SPACING;
-	if c.is_whitespace() {width += size as f32 * EXTRA_WHITESPACE_SPACING;}
-	for [(_prev_c, prev_glyph), (c, glyph)] in glyphs.array_windows() {
-		width += font.kern(prev_glyph.id, glyph.id);
-		width += font.h_advance(glyph.id);
-		width += size as f32 * EXTRA_CHAR_SPACING;
-		if c.is_whitespace() {width += size as f32 * EXTRA_WHITESPACE_SPACING;}
-	}
-	width -= size as f32 * EXTRA_CHAR_SPACING;
-	let mut x = x as f32 + h_align.get_offset(width);
-	let y = y as f32 + v_align.get_offset(font.height());
-	
-	// render first char
-	if let Some((c, first_glyph)) = glyphs.first() {
-		let texture_data = settings.text_cache.map_subpixel.get(&(*c, size, foreground, background));
-		if let Some((texture, width, height, x_offset, y_offset)) = texture_data {
-			let dst = Rect::new((x - *x_offset) as i32, (y - *y_offset) as i32, *width, *height);
-			settings.canvas.copy(texture, None, dst)?;
-		}
-		x += font.h_advance(first_glyph.id);
-		x += size as f32 * EXTRA_CHAR_SPACING;
-		if c.is_whitespace() {x += size as f32 * EXTRA_WHITESPACE_SPACING;}
+		let Some((font_index, phase, (id, pixels, width, height, x_offset, y_offset))) = texture_data else {continue;};
+		if width == 0 || height == 0 {continue;}
+		let cached_glyph = text_cache.upload_glyph(&pixels, width, height, x_offset, y_offset, texture_creator)?;
+		text_cache.map_subpixel.insert((font_index, id, size, foreground, background, phase, order, filter, gamma_correction), cached_glyph);
 	}
-	
-	// render remaining chars (with kerning)
-	for [(_prev_c, prev_glyph), (c, glyph)] in glyphs.array_windows() {
-		x += font.kern(prev_glyph.id, glyph.id);
-		let texture_data = settings.text_cache.map_subpixel.get(&(*c, size, foreground, background));
-		if let Some((texture, width, height, x_offset, y_offset)) = texture_data {
-			let dst = Rect::new((x - *x_offset) as i32, (y - *y_offset) as i32, *width, *height);
-			settings.canvas.copy(texture, None, dst)?;
-		}
-		x += font.h_advance(glyph.id);
-		x += size as f32 * EXTRA_CHAR_SPACING;
-		if c.is_whitespace() {x += size as f32 * EXTRA_WHITESPACE_SPACING;}
-	}
-	
+	text_cache.record_stats(hits, misses, rasterize_time, upload_start.elapsed());
+	text_cache.evict_if_needed();
 	Ok(())
 }
 
 
 
-fn rasterize_glyph_subpixel(glyph: Glyph, c: char, foreground: Color, background: Color, font: &PxScaleFont<&impl ThreadSafeFont>) -> Option<(char, Vec<u8>, u32, u32, f32, f32)> {
+/// Scales the fonts to `size`, with the axis that the sub-pixels are split along tripled
+pub(crate) fn get_subpixel_fonts<F: ThreadSafeFont>(fonts: &[F], size: u32, order: SubpixelOrder) -> Vec<PxScaleFont<&F>> {
+	let mut scale = PxScale::from(size as f32);
+	if order.is_vertical() {
+		scale.y *= 3.0;
+	} else {
+		scale.x *= 3.0;
+	}
+	fonts.iter().map(|font| font.as_scaled(scale)).collect()
+}
+
+/// Gets where the glyph's origin goes when rasterizing at the given sub-pixel position (on the tripled axis when the sub-pixels are horizontal)
+pub(crate) fn get_subpixel_glyph_position(phase: u32, order: SubpixelOrder) -> Point {
+	let phase_offset = get_phase_offset(phase);
+	if order.is_vertical() {
+		point(phase_offset, 0.0)
+	} else {
+		point(phase_offset * 3.0, 0.0)
+	}
+}
+
+pub(crate) fn rasterize_glyph_subpixel(glyph: Glyph, foreground: Color, background: Color, order: SubpixelOrder, filter: SubpixelFilter, gamma_correction: Option<GammaCorrection>, font: &PxScaleFont<&impl ThreadSafeFont>) -> Option<(GlyphId, Vec<u8>, u32, u32, f32, f32)> {
 	
+	let id = glyph.id;
 	let glyph = font.outline_glyph(glyph)?;
 	let bounds = glyph.px_bounds();
+	let vertical = order.is_vertical();
 	
+	let is_dark_text = gamma_correction.is_some_and(|gamma_correction| gamma_correction.get_luminance(foreground) < gamma_correction.get_luminance(background));
 	let foreground = [foreground.r, foreground.g, foreground.b, foreground.a];
 	let background = [background.r, background.g, background.b, background.a];
-	let width = bounds.width().ceil() as u32 / 3 + 3; // Note: this is the width of the final image, not the `channel_datas`
-	let height = bounds.height().ceil() as u32 + 2;
-	let mut channel_datas = vec![0.0; (width * 3 * height) as usize];
+	// everything below works along the "major" axis (the one the sub-pixels are split along, x for horizontal orders and y for vertical orders) and the "minor" axis
+	let (major_min, major_size, minor_min, minor_size) = if vertical {
+		(bounds.min.y, bounds.height(), bounds.min.x, bounds.width())
+	} else {
+		(bounds.min.x, bounds.width(), bounds.min.y, bounds.height())
+	};
+	// the image starts on a whole pixel (a multiple of 3 sub-pixels) so that the major offset is a whole number, with at least 2 sub-pixels of space for the major filter
+	let major_start = (major_min as i32 - 2).div_euclid(3) * 3;
+	let major_space = (major_min as i32 - major_start) as u32;
+	let major_len = (major_space + major_size.ceil() as u32 + 2).div_ceil(3); // Note: this is the length of the final image, not the `channel_datas`
+	let minor_len = minor_size.ceil() as u32 + 2;
+	let row_len = (major_len * 3) as usize;
+	let mut channel_datas = vec![0.0; row_len * minor_len as usize];
 	glyph.draw(|x, y, v| {
-		let (x, y) = (x + 1, y + 1);
-		channel_datas[(x + y * width * 3) as usize] = v.clamp(0.0, 1.0);
+		let (major, minor) = if vertical {(y, x)} else {(x, y)};
+		let (major, minor) = (major + major_space, minor + 1);
+		channel_datas[major as usize + minor as usize * row_len] = v.clamp(0.0, 1.0);
 	});
-	let mut channel_datas_alt = vec![0.0; (width * 3 * height) as usize];
-	const HORIZONTAL_WEIGHTS: [f32; 5] = [0.09526326, 0.55556049, 1.0, 0.55556049, 0.09526326];
-	for x in 0.. (width * 3) as usize {
-		for y in 0.. height as usize {
-			let left_margin = x.min(2);
-			let right_margin = ((width * 3) as usize - 1 - x).min(2);
-			let i = x + y * (width * 3) as usize;
-			let pixels = &channel_datas[i - left_margin ..= i + right_margin];
-			let weights = &HORIZONTAL_WEIGHTS[2 - left_margin ..= 2 + right_margin];
+	let mut channel_datas_alt = vec![0.0; row_len * minor_len as usize];
+	for major in 0..row_len {
+		for minor in 0..minor_len as usize {
+			let low_margin = major.min(2);
+			let high_margin = (row_len - 1 - major).min(2);
+			let i = major + minor * row_len;
+			let pixels = &channel_datas[i - low_margin ..= i + high_margin];
+			let weights = &filter.weights[2 - low_margin ..= 2 + high_margin];
 			let mut total = 0.0;
 			let mut total_weight = 0.0;
 			for (pixel, weight) in pixels.iter().zip(weights) {
 				total += pixel * weight;
 				total_weight += weight;
 			}
-			channel_datas_alt[i] = total / total_weight;
+			channel_datas_alt[i] = if total_weight > 0.0 {total / total_weight} else {0.0};
 		}
 	}
-	for x in 0.. (width * 3) as usize {
-		for y in 0.. height as usize {
+	for major in 0..row_len {
+		for minor in 0..minor_len as usize {
 			let mut total = 0.0;
 			let mut total_weight = 0.0;
-			let i = x + y * (width * 3) as usize;
-			if y > 0 {
-				total += channel_datas_alt[i - (width * 3) as usize] * 0.00504176;
-				total_weight += 0.00504176;
+			let i = major + minor * row_len;
+			if minor > 0 {
+				total += channel_datas_alt[i - row_len] * filter.cross_weight;
+				total_weight += filter.cross_weight;
 			}
 			total += channel_datas_alt[i];
 			total_weight += 1.0;
-			if y < height as usize - 1 {
-				total += channel_datas_alt[i + (width * 3) as usize] * 0.00504176;
-				total_weight += 0.00504176;
+			if minor < minor_len as usize - 1 {
+				total += channel_datas_alt[i + row_len] * filter.cross_weight;
+				total_weight += filter.cross_weight;
 			}
-			channel_datas[i] = (total / total_weight).powf(SUBPIXEL_VALUE_POW);
+			channel_datas[i] = (total / total_weight).powf(filter.gamma);
 		}
 	}
+	
+	// the first sub-pixel along the major axis is red for rgb orders and blue for bgr orders
+	let sub_pixel_indices = if order.is_bgr() {[2, 1, 0]} else {[0, 1, 2]};
+	let (width, height) = if vertical {(minor_len, major_len)} else {(major_len, minor_len)};
 	let mut pixels = vec![0; (width * 4 * height) as usize];
-	for x in 0..width as usize {
-		for y in 0..height as usize {
-			let red_value   = (channel_datas[x * 3     + y * (width * 3) as usize] * 255.0) as u16;
-			let green_value = (channel_datas[x * 3 + 1 + y * (width * 3) as usize] * 255.0) as u16;
-			let blue_value  = (channel_datas[x * 3 + 2 + y * (width * 3) as usize] * 255.0) as u16;
+	for major in 0..major_len as usize {
+		for minor in 0..minor_len as usize {
+			let (x, y) = if vertical {(minor, major)} else {(major, minor)};
+			let red_value   = (channel_datas[major * 3 + sub_pixel_indices[0] + minor * row_len] * 255.0) as u16;
+			let green_value = (channel_datas[major * 3 + sub_pixel_indices[1] + minor * row_len] * 255.0) as u16;
+			let blue_value  = (channel_datas[major * 3 + sub_pixel_indices[2] + minor * row_len] * 255.0) as u16;
 			let alpha_value = (red_value + green_value + blue_value) / 3;
 			let red   = background[0] as u16 * (255 - red_value  ) / 255 + foreground[0] as u16 * red_value   / 255;
 			let green = background[1] as u16 * (255 - green_value) / 255 + foreground[1] as u16 * green_value / 255;
 			let blue  = background[2] as u16 * (255 - blue_value ) / 255 + foreground[2] as u16 * blue_value  / 255;
 			let alpha = background[3] as u16 * (255 - alpha_value) / 255 + foreground[3] as u16 * alpha_value / 255;
+			let (red, green, blue) = match gamma_correction {
+				Some(gamma_correction) => {
+					let blend = |channel: usize, value: u16| gamma_correction.blend(background[channel], foreground[channel], value as f32 / 255.0, is_dark_text) as u16;
+					(blend(0, red_value), blend(1, green_value), blend(2, blue_value))
+				}
+				None => (red, green, blue),
+			};
 			pixels[(x + y * width as usize) * 4    ] = red as u8;
 			pixels[(x + y * width as usize) * 4 + 1] = green as u8;
 			pixels[(x + y * width as usize) * 4 + 2] = blue as u8;
@@ -160,5 +258,8 @@ fn rasterize_glyph_subpixel(glyph: Glyph, c: char, foreground: Color, background
 		}
 	}
 	
-	Some((c, pixels, width, height, -bounds.min.x / 3.0, -bounds.min.y))
+	// the minor axis has 1 pixel of space on each side for the minor filter
+	let (major_offset, minor_offset) = (-(major_start / 3) as f32, 1.0 - minor_min);
+	let (x_offset, y_offset) = if vertical {(minor_offset, major_offset)} else {(major_offset, minor_offset)};
+	Some((id, pixels, width, height, x_offset, y_offset))
 }
@@ -0,0 +1,84 @@
+use easy_sdl3_text::*;
+use ab_glyph::FontRef;
+
+
+
+fn load_font() -> FontRef<'static> {
+	FontRef::try_from_slice(include_bytes!("../examples/resources/Inter_24pt-Regular.ttf")).unwrap()
+}
+
+fn load_fallback_font() -> FontRef<'static> {
+	FontRef::try_from_slice(include_bytes!("resources/DejaVuSans.ttf")).unwrap()
+}
+
+/// Wraps the text so that `fits` is just able to fit on one line, and checks that it was split into the expected lines (by comparing the widths, since each wrapped line is laid out on its own)
+fn assert_wraps_into(text: &str, fits: &str, expected_lines: &[&str]) {
+	let cache = TextCache::new(load_font());
+	let max_width = cache.measure_text(fits, 20.0).width + 0.5;
+	let expected_width = expected_lines.iter().map(|line| cache.measure_text(line, 20.0).width).fold(0.0, f32::max);
+	let metrics = cache.measure_text_with(text, 20.0, 1.0, Some(max_width));
+	assert_eq!(metrics.line_count, expected_lines.len(), "{text:?}");
+	assert!((metrics.width - expected_width).abs() < 0.01, "{text:?}: {} != {expected_width}", metrics.width);
+}
+
+
+
+#[test]
+fn wrapping_never_breaks_at_non_breaking_spaces() {
+	// breaking at the non-breaking space would give "aaaaaa bb" and "c"
+	assert_wraps_into("aaaaaa bb\u{A0}c", "aaaaaa bb", &["aaaaaa", "bb\u{A0}c"]);
+}
+
+#[test]
+fn wrapping_at_a_soft_hyphen_shows_a_hyphen() {
+	assert_wraps_into("abcdefg\u{AD}hi", "abcdefg-", &["abcdefg-", "hi"]);
+	// soft hyphens are invisible when a line isn't broken at them
+	let cache = TextCache::new(load_font());
+	let (with_soft_hyphen, without_soft_hyphen) = (cache.measure_text("abc\u{AD}defgh", 20.0), cache.measure_text("abcdefgh", 20.0));
+	assert!((with_soft_hyphen.width - without_soft_hyphen.width).abs() < 0.01);
+}
+
+#[test]
+fn wrapping_breaks_between_cjk_and_latin_text() {
+	assert_wraps_into("\u{4F60}\u{597D}world", "\u{4F60}\u{597D}wo", &["\u{4F60}\u{597D}", "world"]);
+}
+
+#[test]
+fn wrapping_breaks_after_hyphens() {
+	assert_wraps_into("well-known", "well-kno", &["well-", "known"]);
+}
+
+#[test]
+fn wrapping_keeps_combining_marks_with_their_base() {
+	let cache = TextCache::new(load_font());
+	let metrics = cache.measure_text_with("e\u{301}e\u{301}", 20.0, 1.0, Some(0.0));
+	assert_eq!(metrics.line_count, 2);
+}
+
+#[test]
+#[cfg(feature = "shaping")]
+fn wraps_right_to_left_text_with_shaping() {
+	let cache = TextCache::new(load_font());
+	let metrics = cache.measure_text_with("שלום עולם hello שלום", 20.0, 1.0, Some(80.0));
+	assert!(metrics.line_count > 1);
+}
+
+#[test]
+fn fallback_fonts_are_only_used_for_chars_the_main_font_lacks() {
+	let cache = TextCache::new_with_fallbacks(load_font(), vec!(load_fallback_font()));
+	// the knight is only in the fallback font, and the chars after it should go back to the main font (with and without `shaping`)
+	let with_fallback = cache.measure_text("\u{265E}abc", 20.0);
+	let without_fallback = cache.measure_text("abc", 20.0);
+	assert_eq!(with_fallback.advances[1..], without_fallback.advances[..]);
+}
+
+#[test]
+fn advances_are_in_visual_order() {
+	let cache = TextCache::new_with_fallbacks(load_font(), vec!(load_fallback_font()));
+	// alef is much wider than vav, and the right-to-left run is drawn (and measured) vav first
+	let (alef, vav) = (cache.measure_text("\u{5D0}", 20.0).advances[0], cache.measure_text("\u{5D5}", 20.0).advances[0]);
+	assert!(alef - vav > 2.0);
+	let advances = cache.measure_text("ab \u{5D0}\u{5D5}", 20.0).advances;
+	assert_eq!(advances.len(), 5);
+	assert!((advances[3] - vav).abs() < 0.01 && (advances[4] - alef).abs() < 0.01, "{advances:?}");
+}
@@ -0,0 +1,42 @@
+use easy_sdl3_text::*;
+use ab_glyph::FontRef;
+
+
+
+fn load_cache() -> TextCache<'static, FontRef<'static>> {
+	let font = FontRef::try_from_slice(include_bytes!("../examples/resources/Inter_24pt-Regular.ttf")).unwrap();
+	let fallback_font = FontRef::try_from_slice(include_bytes!("resources/DejaVuSans.ttf")).unwrap();
+	TextCache::new_with_fallbacks(font, vec!(fallback_font))
+}
+
+/// The leftmost and rightmost columns that have any ink between the two rows
+fn ink_columns(text: &RasterizedText, rows: std::ops::Range<u32>) -> (u32, u32) {
+	let (mut min_x, mut max_x) = (u32::MAX, 0);
+	for y in rows {
+		for x in 0..text.width {
+			if text.pixels[((x + y * text.width) * 4 + 3) as usize] > 0 {
+				min_x = min_x.min(x);
+				max_x = max_x.max(x + 1);
+			}
+		}
+	}
+	(min_x, max_x)
+}
+
+
+
+#[test]
+fn start_alignment_follows_each_paragraphs_direction() {
+	let cache = load_cache();
+	let text = rasterize_text_regular("שלום\nab", 20.0, 1.0, None, HAlign::Start, (0, 0, 0), None, &cache);
+	let lines_height = text.metrics.height - (text.metrics.ascent - text.metrics.descent);
+	let first_baseline = text.baseline as u32;
+	let second_baseline = (text.baseline + lines_height) as u32;
+	let x_height = (text.metrics.ascent * 0.5) as u32;
+	
+	// the right-to-left line ends at the anchor, and the left-to-right line starts there
+	let (_first_min, first_max) = ink_columns(&text, first_baseline - x_height .. first_baseline);
+	let (second_min, _second_max) = ink_columns(&text, second_baseline - x_height .. second_baseline);
+	assert!(first_max as f32 <= text.anchor_x + 1.0, "{first_max} {}", text.anchor_x);
+	assert!(second_min as f32 >= text.anchor_x - 1.0, "{second_min} {}", text.anchor_x);
+}